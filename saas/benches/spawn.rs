@@ -0,0 +1,32 @@
+#![feature(test)]
+
+extern crate saas;
+extern crate test;
+
+use saas::state::*;
+use saas::util::*;
+
+use test::Bencher;
+
+// Picking a spawn position used to scan the grid row by row, so these
+// are run on a large map to keep an eye on the cost of spawning.
+
+#[bench]
+fn spawn_snake_1000x1000(b: &mut Bencher) {
+    let mut st = GameState::builder().with_dimensions(1000, 1000).build();
+
+    b.iter(|| {
+        let id = st.add_snake().unwrap();
+        st.remove_snake(id).unwrap();
+    });
+}
+
+#[bench]
+fn spawn_prop_1000x1000(b: &mut Bencher) {
+    let mut st = GameState::builder()
+        .with_dimensions(1000, 1000)
+        .with_prop_spawn_timer(Timer::new(0))
+        .build();
+
+    b.iter(|| st.tick());
+}
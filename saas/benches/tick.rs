@@ -0,0 +1,51 @@
+#![feature(test)]
+
+extern crate saas;
+extern crate test;
+
+use saas::state::*;
+
+use test::Bencher;
+
+fn crowded(rows: usize, cols: usize, snakes: usize) -> GameState {
+    let mut st = GameState::builder().with_dimensions(rows, cols).build();
+
+    for _ in 0..snakes {
+        st.add_snake().unwrap();
+    }
+
+    // let the snakes leave their spawn protection and grow a bit
+    for _ in 0..20 {
+        st.tick();
+        st.get_game_data();
+    }
+
+    st
+}
+
+#[bench]
+fn tick_256_snakes_128x128(b: &mut Bencher) {
+    let mut st = crowded(128, 128, 256);
+
+    b.iter(|| {
+        st.tick();
+        st.get_game_data()
+    });
+}
+
+#[bench]
+fn tick_256_snakes_512x512(b: &mut Bencher) {
+    let mut st = crowded(512, 512, 256);
+
+    b.iter(|| {
+        st.tick();
+        st.get_game_data()
+    });
+}
+
+#[bench]
+fn clone_256_snakes_512x512(b: &mut Bencher) {
+    let st = crowded(512, 512, 256);
+
+    b.iter(|| st.clone());
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::num::NonZeroUsize;
 
@@ -9,6 +10,8 @@ use serde::{Deserialize, Serialize};
 pub type ID = u64;
 pub type SnakeID = u64;
 pub type PropID = u64;
+pub type TeamID = u64;
+pub type HazardID = u64;
 
 pub struct SnakeData {}
 
@@ -50,6 +53,18 @@ pub enum Cmd {
     GiveScore(i32),
     GiveBuff(BuffPtr),
     Grow(NonZeroUsize),
+    // changes the speed of the snake for the given number of ticks
+    ChangeSpeed(Speed, NonZeroUsize),
+    // cuts cells off the tail, a snake always keeps one body cell
+    Shrink(NonZeroUsize),
+    // the tail becomes the head and the head the tail
+    Reverse,
+    // only if the cell is vacant, the body follows through the gap
+    TeleportTo(Index2D),
+    // the snake stands still for the given number of ticks
+    Freeze(NonZeroUsize),
+    // cuts the body at the given cell, see Rules::tail_cutting
+    Sever(Index2D),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -63,12 +78,54 @@ pub enum PropEvent {
     Remove(PropID),
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Safe,
+    // about to turn lethal
+    Warning,
+    Lethal,
+}
+
+// Where a cell is within a prop, relative to the prop's top left cell.
+// Props that cover one cell only have Part { di: 0, dj: 0 }.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Part {
+    pub di: u32,
+    pub dj: u32,
+}
+
+impl Part {
+    pub fn new(di: u32, dj: u32) -> Self {
+        Part {
+            di: di,
+            dj: dj,
+        }
+    }
+
+    pub fn origin() -> Self {
+        Part::new(0, 0)
+    }
+
+    pub fn rect(rows: u32, cols: u32) -> Vec<Part> {
+        let mut parts = Vec::with_capacity((rows * cols) as usize);
+
+        for di in 0..rows {
+            for dj in 0..cols {
+                parts.push(Part::new(di, dj));
+            }
+        }
+
+        parts
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub enum Kind {
     None,
-    Prop,
+    Prop(Part),
     SnakeHead,
     SnakeBody,
+    Hazard(Phase),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -83,15 +140,96 @@ pub enum CollisionResult {
     RemoveOther,
 }
 
+// A square of the grid centered on a prop, see Prop::tick.
+pub struct Surroundings {
+    radius: usize,
+    tags: Vec<Tag>,
+}
+
+impl Surroundings {
+    // tags are row-major and 2 * radius + 1 cells wide
+    pub fn new(radius: usize, tags: Vec<Tag>) -> Self {
+        Surroundings {
+            radius: radius,
+            tags: tags,
+        }
+    }
+
+    pub fn radius(&self) -> usize {
+        self.radius
+    }
+
+    // di and dj are relative to the prop and within the radius.
+    pub fn get(&self, di: i32, dj: i32) -> Tag {
+        let r = self.radius as i32;
+        let w = 2 * r + 1;
+        self.tags[((di + r) * w + dj + r) as usize]
+    }
+}
+
 pub trait Prop : Send {
     fn collision_result(&self) -> CollisionResult;
     fn collision_events(&self, id: SnakeID) -> Vec<SnakeEvent>;
     fn get_timer(&self) -> Option<Timer>;
     fn id(&self) -> ID;
+
+    // Props are cloned along with the GameState they are in, usually
+    // implemented as Box::new(self.clone()).
+    fn box_clone(&self) -> Box<Prop>;
+
+    // The cells the prop covers, the first one is where it is placed. A
+    // snake running into any of them collides with the whole prop.
+    fn shape(&self) -> Vec<Part> {
+        vec![Part::origin()]
+    }
+
+    // Props that return Some are ticked every tick and see this many cells
+    // around them.
+    fn sight(&self) -> Option<usize> {
+        None
+    }
+
+    // Returns the direction to move in, if any. The prop only moves if the
+    // cell it moves to is vacant.
+    fn tick(&mut self, _near: &Surroundings) -> Option<Direction> {
+        None
+    }
+}
+
+impl Clone for Box<Prop> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+// What the collision rules need to know about the game being played.
+pub struct Rules<'a> {
+    // whether running into a teammate's body is lethal
+    pub friendly_fire: bool,
+    // Whether running into another snake's body severs it instead of
+    // killing the head. Running into your own body is lethal either way.
+    pub tail_cutting: bool,
+    pub teams: &'a HashMap<SnakeID, TeamID>,
+}
+
+impl<'a> Rules<'a> {
+    fn is_lethal_body(&self, head: SnakeID, body: SnakeID) -> bool {
+        if self.friendly_fire || head == body {
+            return true;
+        }
+
+        match (self.teams.get(&head), self.teams.get(&body)) {
+            (Some(a), Some(b)) => a != b,
+            _ => true,
+        }
+    }
 }
 
 pub enum Entity {
     Prop(PropID, Box<Prop>),
+    // Hazards lie below snakes and props and stay in their cell, heads
+    // only die on them while they are lethal.
+    Hazard(HazardID, Phase),
     SnakeBody(SnakeID),
     SnakeHead(SnakeID),
     ImmortalSnakeHead(SnakeID),
@@ -101,6 +239,11 @@ impl fmt::Debug for Entity {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Entity::Prop(_, p) => write!(f, "Entity::Prop(id: {:?})", p.id()),
+
+            Entity::Hazard(id, phase) => {
+                write!(f, "Entity::Hazard(id: {}, phase: {:?})", id, phase)
+            }
+
             Entity::SnakeBody(id) => write!(f, "Entity::SnakeBody(id: {})", id),
             Entity::SnakeHead(id) => write!(f, "Entity::SnakeHead(id: {})", id),
 
@@ -126,9 +269,19 @@ impl Entity {
         }
     }
 
+    pub fn is_hazard(&self) -> bool {
+        match self {
+            Entity::Hazard(_, _) => true,
+            _ => false,
+        }
+    }
+
+    // idx is where the collision happens.
     fn collide(
         a: Entity,
         b: Entity,
+        idx: Index2D,
+        rules: &Rules,
     ) -> (Option<Entity>, Vec<PropEvent>, Vec<SnakeEvent>) {
         let (a, b) = if a.is_snake_head() { (a, b) } else { (b, a) };
 
@@ -162,7 +315,27 @@ impl Entity {
                 }
             }
 
-            Entity::SnakeBody(_) | Entity::ImmortalSnakeHead(_) => {
+            Entity::SnakeBody(id2) => {
+                // a head passing over a teammate's body lies on top of it
+                // until it moves on
+                if !rules.is_lethal_body(id, id2) {
+                    out = Some(a);
+                } else if rules.tail_cutting && id != id2 {
+                    snake_evs.push(SnakeEvent {
+                        id: id2,
+                        cmd: Cmd::Sever(idx),
+                    });
+                    out = Some(a);
+                } else {
+                    snake_evs.push(SnakeEvent {
+                        id: id,
+                        cmd: Cmd::Kill,
+                    });
+                    out = Some(b);
+                }
+            }
+
+            Entity::ImmortalSnakeHead(_) => {
                 snake_evs.push(SnakeEvent {
                     id: id,
                     cmd: Cmd::Kill,
@@ -181,6 +354,11 @@ impl Entity {
                 });
                 out = None;
             }
+
+            // see Entity::collide_many
+            x @ Entity::Hazard(_, _) => {
+                panic!("error @ collide: {:?}; {:?}", a, x)
+            }
         }
 
         (out, prop_evs, snake_evs)
@@ -203,8 +381,42 @@ impl Entity {
         out
     }
 
+    // A hazard lies below the rest of the cell, which collides as if it
+    // was not there. Only when nothing is left on top is the hazard
+    // returned.
     pub fn collide_many(
+        ens: Vec<Entity>,
+        idx: Index2D,
+        rules: &Rules,
+    ) -> (Option<Entity>, Vec<PropEvent>, Vec<SnakeEvent>) {
+        let (mut hazards, mut ens): (Vec<Entity>, Vec<Entity>) =
+            ens.into_iter().partition(Entity::is_hazard);
+
+        let hazard = hazards.pop();
+        if !hazards.is_empty() {
+            panic!("error @ collide_many: {:?}; {:?}", hazard, hazards);
+        }
+
+        // heads die on a lethal hazard, whatever else is in the cell
+        let mut snake_evs = Vec::new();
+        if let Some(Entity::Hazard(_, Phase::Lethal)) = hazard {
+            let (heads, rest) =
+                ens.into_iter().partition(Entity::is_snake_head);
+            snake_evs = Entity::collide_snake_heads(heads);
+            ens = rest;
+        }
+
+        let (out, prop_evs, snake_evs2) =
+            Entity::collide_above(ens, idx, rules);
+        snake_evs.extend(snake_evs2);
+
+        (out.or(hazard), prop_evs, snake_evs)
+    }
+
+    fn collide_above(
         mut ens: Vec<Entity>,
+        idx: Index2D,
+        rules: &Rules,
     ) -> (Option<Entity>, Vec<PropEvent>, Vec<SnakeEvent>) {
         let n = ens.len();
 
@@ -213,7 +425,8 @@ impl Entity {
         } else if n == 1 {
             return (Some(ens.swap_remove(0)), Vec::new(), Vec::new());
         } else if n == 2 {
-            return Entity::collide(ens.swap_remove(0), ens.swap_remove(0));
+            let (a, b) = (ens.swap_remove(0), ens.swap_remove(0));
+            return Entity::collide(a, b, idx, rules);
         }
 
         let heads: Vec<Entity> =
@@ -272,10 +485,15 @@ impl Entity {
     pub fn tag(&self) -> Tag {
         match self {
             Entity::Prop(_, p) => Tag {
-                kind: Kind::Prop,
+                kind: Kind::Prop(Part::origin()),
                 id: p.id(),
             },
 
+            Entity::Hazard(id, phase) => Tag {
+                kind: Kind::Hazard(*phase),
+                id: *id,
+            },
+
             Entity::SnakeBody(id) => Tag {
                 kind: Kind::SnakeBody,
                 id: *id,
@@ -1,5 +1,8 @@
 #![feature(drain_filter)]
 
 pub mod entity;
+pub mod map;
+pub mod rollback;
+pub mod scenario;
 pub mod state;
 pub mod util;
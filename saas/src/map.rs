@@ -0,0 +1,188 @@
+use crate::entity::Phase;
+use crate::util::*;
+
+// +++++++++
+// + Cycle +
+// +++++++++
+
+// How many ticks a hazard stays in each phase. A hazard starts out safe,
+// `offset` ticks into its cycle, which lets a row of hazards sweep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub safe: usize,
+    pub warning: usize,
+    pub lethal: usize,
+    pub offset: usize,
+}
+
+impl Cycle {
+    pub fn spikes() -> Self {
+        Cycle {
+            safe: 8,
+            warning: 2,
+            lethal: 4,
+            offset: 0,
+        }
+    }
+
+    pub fn laser(offset: usize) -> Self {
+        Cycle {
+            safe: 7,
+            warning: 2,
+            lethal: 1,
+            offset: offset,
+        }
+    }
+
+    // always lethal
+    pub fn wall() -> Self {
+        Cycle {
+            safe: 0,
+            warning: 0,
+            lethal: 1,
+            offset: 0,
+        }
+    }
+
+    pub fn phase_at(&self, ticks: usize) -> Phase {
+        let period = self.safe + self.warning + self.lethal;
+        if period == 0 {
+            return Phase::Safe;
+        }
+
+        let t = (ticks + self.offset) % period;
+        if t < self.safe {
+            Phase::Safe
+        } else if t < self.safe + self.warning {
+            Phase::Warning
+        } else {
+            Phase::Lethal
+        }
+    }
+}
+
+// +++++++
+// + Map +
+// +++++++
+
+pub struct Map {
+    pub rows: usize,
+    pub cols: usize,
+    pub hazards: Vec<(Index2D, Cycle)>,
+}
+
+const SPIKES: &str = "
+....................
+....................
+....................
+....................
+....^^........^^....
+....^..........^....
+....................
+....................
+....................
+....................
+....................
+....................
+....................
+....................
+....^..........^....
+....^^........^^....
+....................
+....................
+....................
+....................
+";
+
+const LASER: &str = "
+######........######
+....................
+....................
+....................
+....................
+....................
+....................
+....................
+....................
+01234567890123456789
+....................
+....................
+....................
+....................
+....................
+....................
+....................
+....................
+....................
+######........######
+";
+
+impl Map {
+    pub fn empty(rows: usize, cols: usize) -> Self {
+        Map {
+            rows: rows,
+            cols: cols,
+            hazards: Vec::new(),
+        }
+    }
+
+    // Parses a map drawn with one character per cell:
+    //  .   an empty cell
+    //  ^   spikes
+    //  0-9 a laser, offset by that many ticks
+    //  #   a wall
+    // Blank lines are skipped, all other lines must be equally long.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = s
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        let rows = lines.len();
+        let cols = lines.first().map(|line| line.chars().count()).unwrap_or(0);
+
+        if rows == 0 || cols == 0 {
+            return Err("the map is empty".to_string());
+        }
+
+        let mut hazards = Vec::new();
+
+        for (i, line) in lines.iter().enumerate() {
+            if line.chars().count() != cols {
+                return Err(format!("line {} is not {} cells long", i, cols));
+            }
+
+            for (j, c) in line.chars().enumerate() {
+                let cycle = match c {
+                    '.' => continue,
+                    '^' => Cycle::spikes(),
+                    '#' => Cycle::wall(),
+                    '0'..='9' => Cycle::laser(c as usize - '0' as usize),
+                    _ => return Err(format!("unknown map symbol {:?}", c)),
+                };
+
+                hazards.push((Index2D::new(i, j), cycle));
+            }
+        }
+
+        Ok(Map {
+            rows: rows,
+            cols: cols,
+            hazards: hazards,
+        })
+    }
+
+    pub fn builtin_names() -> &'static [&'static str] {
+        &["arena", "spikes", "laser"]
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "arena" => Some(Map::empty(20, 20)),
+            "spikes" => Map::parse(SPIKES).ok(),
+            "laser" => Map::parse(LASER).ok(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+
+use crate::entity::*;
+use crate::state::*;
+use crate::util::*;
+
+extern crate serde;
+use serde::{Deserialize, Serialize};
+
+// +++++++++
+// + Input +
+// +++++++++
+
+// What a player can do to their snake between two ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Input {
+    Direction(Direction),
+    Boost,
+}
+
+impl Input {
+    // Fails like the GameState method it stands for.
+    pub fn apply(&self, st: &mut GameState, id: SnakeID) -> Result<(), ()> {
+        match self {
+            Input::Direction(dir) => st.give_direction(id, *dir),
+            Input::Boost => st.boost(id),
+        }
+    }
+}
+
+// ++++++++++++
+// + Rollback +
+// ++++++++++++
+
+// A GameState along with copies of it from the last few ticks, so that it
+// can be rewound to one of them and played forward again with other inputs.
+// Random events, such as where props spawn, are rolled again when playing
+// forward.
+pub struct Rollback {
+    state: GameState,
+    // the state at the start of each of the last ticks, oldest first
+    history: VecDeque<GameState>,
+    capacity: usize,
+}
+
+impl Rollback {
+    // Keeps the states of the last capacity ticks.
+    pub fn new(state: GameState, capacity: usize) -> Self {
+        Rollback {
+            state: state,
+            history: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    // Ticking the state directly leaves a gap in the history that can not
+    // be rewound across, use Rollback::tick instead.
+    pub fn state_mut(&mut self) -> &mut GameState {
+        &mut self.state
+    }
+
+    // The oldest tick that can be rewound to.
+    pub fn oldest(&self) -> u64 {
+        self.history
+            .front()
+            .map(|st| st.ticks())
+            .unwrap_or(self.state.ticks())
+    }
+
+    pub fn tick(&mut self) {
+        // a paused game does not advance, so there is nothing to keep
+        if self.state.is_paused() {
+            return;
+        }
+
+        self.save();
+        self.state.tick();
+    }
+
+    pub fn step(&mut self) {
+        self.save();
+        self.state.step();
+    }
+
+    fn save(&mut self) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(self.state.clone());
+    }
+
+    // Goes back to the start of the given tick, dropping every state after
+    // it. Fails if the tick is no longer or not yet kept.
+    pub fn rewind(&mut self, tick: u64) -> Result<(), ()> {
+        if tick == self.state.ticks() {
+            return Ok(());
+        }
+
+        let k = self
+            .history
+            .iter()
+            .position(|st| st.ticks() == tick)
+            .ok_or(())?;
+
+        self.history.truncate(k + 1);
+        self.state = self.history.pop_back().unwrap();
+        Ok(())
+    }
+
+    // Plays forward one tick for each element of inputs, after applying
+    // the inputs for that tick. Inputs that fail, e.g. for snakes that
+    // have been removed, are skipped.
+    pub fn resimulate(&mut self, inputs: &[Vec<(SnakeID, Input)>]) {
+        for tick_inputs in inputs {
+            for (id, input) in tick_inputs {
+                input.apply(&mut self.state, *id).ok();
+            }
+
+            self.step();
+        }
+    }
+}
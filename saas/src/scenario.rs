@@ -0,0 +1,311 @@
+use std::collections::HashSet;
+
+use crate::entity::*;
+use crate::map::*;
+use crate::rollback::*;
+use crate::state::*;
+use crate::util::*;
+
+// ++++++++++++
+// + Scenario +
+// ++++++++++++
+
+// Builds a game from a picture, for writing tests against. One character
+// per cell:
+//  .        an empty cell
+//  #        a wall
+//  =        a hazard that is never lethal
+//  *        food that makes snakes grow
+//  x        food that kills
+//  + -      food that makes snakes faster, or slower
+//  m        a mouse, which runs from snakes
+//  b        a bonus, which wanders around
+//  $        a golden apple, drawn on each of the 2x2 cells it covers
+//  ^ v < >  the head of a snake, looking that way
+//  o        a body cell
+// Bodies are followed from the head to the tail, so snakes should not
+// touch. Snakes get their ids in the order their heads appear, row by row,
+// and with teams they take turns joining them.
+// Pictures of the game being played also draw spawning heads as @, other
+// props as ? and hazards that are not lethal as =. No props spawn by
+// themselves.
+pub struct Scenario {
+    state: GameState,
+    // everything that happened since the last Scenario::assert_events
+    events: Vec<GameEvent>,
+}
+
+fn lines(picture: &str) -> Vec<&str> {
+    picture
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn head_direction(c: char) -> Option<Direction> {
+    match c {
+        '^' => Some(Direction::Up),
+        'v' => Some(Direction::Down),
+        '<' => Some(Direction::Left),
+        '>' => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+impl Scenario {
+    pub fn new(picture: &str) -> Self {
+        Scenario::with_builder(picture, GameState::builder())
+    }
+
+    // For playing by other rules than the default ones. The map and the
+    // prop spawn timer are always taken from the picture.
+    pub fn with_builder(picture: &str, builder: GameStateBuilder) -> Self {
+        let cells: Vec<Vec<char>> = lines(picture)
+            .iter()
+            .map(|line| line.chars().collect())
+            .collect();
+
+        let rows = cells.len();
+        let cols = cells.first().map(|line| line.len()).unwrap_or(0);
+
+        if rows == 0 || cols == 0 {
+            panic!("error @ Scenario::new: the picture is empty");
+        }
+
+        if cells.iter().any(|line| line.len() != cols) {
+            panic!("error @ Scenario::new: lines are not equally long");
+        }
+
+        let mut map = Map::empty(rows, cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                let cycle = match cells[i][j] {
+                    '#' => Cycle::wall(),
+                    '=' => Cycle {
+                        safe: 1,
+                        warning: 0,
+                        lethal: 0,
+                        offset: 0,
+                    },
+                    _ => continue,
+                };
+
+                map.hazards.push((Index2D::new(i, j), cycle));
+            }
+        }
+
+        let mut state = builder
+            .with_map(map)
+            .with_prop_spawn_timer(Timer::new(std::usize::MAX))
+            .build();
+
+        let mut claimed = HashSet::new();
+        let mut covered = HashSet::new();
+
+        for i in 0..rows {
+            for j in 0..cols {
+                let idx = Index2D::new(i, j);
+
+                match cells[i][j] {
+                    '*' => state.place_prop(idx, Box::new(Food::GrowFood)),
+                    'x' => state.place_prop(idx, Box::new(Food::BadFood)),
+                    '+' => state.place_prop(idx, Box::new(Food::FastFood)),
+                    '-' => state.place_prop(idx, Box::new(Food::SlowFood)),
+
+                    'm' => {
+                        let mouse = Mouse { is_resting: false };
+                        state.place_prop(idx, Box::new(mouse))
+                    }
+
+                    'b' => state.place_prop(idx, Box::new(Bonus { ticks: 0 })),
+
+                    // the first $ of an apple is its top left cell
+                    '$' if covered.contains(&idx) => (),
+                    '$' => {
+                        for part in GoldenApple.shape() {
+                            let (di, dj) = (part.di as usize, part.dj as usize);
+                            covered.insert(Index2D::new(i + di, j + dj));
+                        }
+                        state.place_prop(idx, Box::new(GoldenApple));
+                    }
+
+                    '.' | '#' | '=' | 'o' => (),
+
+                    c => match head_direction(c) {
+                        Some(dir) => {
+                            let body =
+                                follow_body(&cells, idx, dir, &mut claimed);
+                            state.place_snake(idx, dir, body);
+                        }
+
+                        None => panic!(
+                            "error @ Scenario::new: unknown symbol {:?}",
+                            c
+                        ),
+                    },
+                }
+            }
+        }
+
+        Scenario {
+            state: state,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut GameState {
+        &mut self.state
+    }
+
+    // Plays one tick for each element of inputs, after applying the inputs
+    // for that tick. Inputs that fail are skipped.
+    pub fn run(&mut self, inputs: &[Vec<(SnakeID, Input)>]) {
+        for tick_inputs in inputs {
+            for (id, input) in tick_inputs {
+                input.apply(&mut self.state, *id).ok();
+            }
+
+            // a paused game keeps the events of the last tick it played
+            let ticks = self.state.ticks();
+            self.state.tick();
+            if self.state.ticks() != ticks {
+                self.events.extend_from_slice(self.state.events());
+            }
+        }
+    }
+
+    pub fn wait(&mut self, ticks: usize) {
+        self.run(&vec![Vec::new(); ticks]);
+    }
+
+    pub fn picture(&self) -> String {
+        let mut lines = Vec::with_capacity(self.state.rows());
+
+        for i in 0..self.state.rows() {
+            let line: String = (0..self.state.cols())
+                .map(|j| self.symbol(Index2D::new(i, j)))
+                .collect();
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+
+    fn symbol(&self, idx: Index2D) -> char {
+        let tag = self.state.tag_at(idx);
+
+        match tag.kind {
+            Kind::None => '.',
+            Kind::Hazard(Phase::Lethal) => '#',
+            Kind::Hazard(_) => '=',
+            Kind::SnakeBody => 'o',
+
+            Kind::Prop(_) => match tag.id {
+                0 => '*',
+                1 => 'x',
+                2 => '+',
+                3 => '-',
+                4 => 'm',
+                5 => 'b',
+                6 => '$',
+                _ => '?',
+            },
+
+            Kind::SnakeHead => match self.state.snake(tag.id) {
+                Some(ref sn) if sn.is_spawning() => '@',
+
+                Some(sn) => match sn.direction() {
+                    Direction::Up => '^',
+                    Direction::Down => 'v',
+                    Direction::Left => '<',
+                    Direction::Right => '>',
+                },
+
+                None => '?',
+            },
+        }
+    }
+
+    pub fn assert_picture(&self, expected: &str) {
+        let expected = lines(expected).join("\n");
+        let ticks = self.state.ticks();
+
+        assert_eq!(
+            self.picture(),
+            expected,
+            "\nafter {} ticks the game looks like\n{}\ninstead of\n{}\n",
+            ticks,
+            self.picture(),
+            expected,
+        );
+    }
+
+    // Compares, in any order, with the events since the last call.
+    pub fn assert_events(&mut self, expected: &[GameEvent]) {
+        let mut expected = expected.to_vec();
+        let mut events: Vec<GameEvent> = self.events.drain(..).collect();
+
+        expected.sort();
+        events.sort();
+        assert_eq!(events, expected);
+    }
+}
+
+// The body cells attached to the head at idx, from the neck to the tail.
+// Bodies are not followed across the edges of the picture.
+fn follow_body(
+    cells: &[Vec<char>],
+    idx: Index2D,
+    dir: Direction,
+    claimed: &mut HashSet<Index2D>,
+) -> Vec<Index2D> {
+    let (rows, cols) = (cells.len(), cells[0].len());
+
+    let neighbors = |idx: Index2D| {
+        let (i, j) = idx.get();
+        let mut out = Vec::with_capacity(4);
+
+        if i > 0 {
+            out.push(Index2D::new(i - 1, j));
+        }
+        if i + 1 < rows {
+            out.push(Index2D::new(i + 1, j));
+        }
+        if j > 0 {
+            out.push(Index2D::new(i, j - 1));
+        }
+        if j + 1 < cols {
+            out.push(Index2D::new(i, j + 1));
+        }
+
+        out
+    };
+
+    // the cell in front of the head is not part of its body
+    let ahead = idx.neighbor(&dir);
+
+    let mut body = Vec::new();
+    let mut curr = idx;
+
+    loop {
+        let next = neighbors(curr).into_iter().find(|x| {
+            let (i, j) = x.get();
+            cells[i][j] == 'o' && !claimed.contains(x) && *x != ahead
+        });
+
+        match next {
+            Some(x) => {
+                claimed.insert(x);
+                body.push(x);
+                curr = x;
+            }
+
+            None => return body,
+        }
+    }
+}
@@ -3,6 +3,7 @@ use std::collections::VecDeque;
 use std::num::NonZeroUsize;
 
 use crate::entity::*;
+use crate::map::*;
 use crate::util::*;
 
 extern crate rand;
@@ -15,16 +16,24 @@ use serde::{Deserialize, Serialize};
 // + default implementation of Prop +
 // ++++++++++++++++++++++++++++++++++
 
-enum Food {
+#[derive(Clone)]
+pub(crate) enum Food {
     GrowFood,
     BadFood,
+    FastFood,
+    SlowFood,
 }
 
+// how long the speed change from FastFood and SlowFood lasts
+const FOOD_SPEED_TICKS: usize = 24;
+
 impl Prop for Food {
     fn collision_result(&self) -> CollisionResult {
         match self {
             Food::BadFood => CollisionResult::RemoveBoth,
             Food::GrowFood => CollisionResult::RemoveSelf,
+            Food::FastFood => CollisionResult::RemoveSelf,
+            Food::SlowFood => CollisionResult::RemoveSelf,
         }
     }
 
@@ -44,6 +53,26 @@ impl Prop for Food {
                     cmd: Cmd::GiveScore(1),
                 },
             ],
+            Food::FastFood => vec![
+                SnakeEvent {
+                    id: id,
+                    cmd: Cmd::ChangeSpeed(
+                        Speed::Fast(NonZeroUsize::new(2).unwrap()),
+                        NonZeroUsize::new(FOOD_SPEED_TICKS).unwrap(),
+                    ),
+                },
+                SnakeEvent {
+                    id: id,
+                    cmd: Cmd::GiveScore(1),
+                },
+            ],
+            Food::SlowFood => vec![SnakeEvent {
+                id: id,
+                cmd: Cmd::ChangeSpeed(
+                    Speed::Slow(NonZeroUsize::new(2).unwrap()),
+                    NonZeroUsize::new(FOOD_SPEED_TICKS).unwrap(),
+                ),
+            }],
         }
     }
 
@@ -55,123 +84,929 @@ impl Prop for Food {
         match self {
             Food::GrowFood => 0,
             Food::BadFood => 1,
+            Food::FastFood => 2,
+            Food::SlowFood => 3,
+        }
+    }
+
+    fn box_clone(&self) -> Box<Prop> {
+        Box::new(self.clone())
+    }
+}
+
+// Runs away from nearby heads, but has to catch its breath every other
+// tick so that it can be caught.
+#[derive(Clone)]
+pub(crate) struct Mouse {
+    pub(crate) is_resting: bool,
+}
+
+const MOUSE_SIGHT: usize = 3;
+
+impl Prop for Mouse {
+    fn collision_result(&self) -> CollisionResult {
+        CollisionResult::RemoveSelf
+    }
+
+    fn collision_events(&self, id: SnakeID) -> Vec<SnakeEvent> {
+        vec![
+            SnakeEvent {
+                id: id,
+                cmd: Cmd::Grow(NonZeroUsize::one()),
+            },
+            SnakeEvent {
+                id: id,
+                cmd: Cmd::GiveScore(2),
+            },
+        ]
+    }
+
+    fn get_timer(&self) -> Option<Timer> {
+        Some(Timer::new(40))
+    }
+
+    fn id(&self) -> ID {
+        4
+    }
+
+    fn box_clone(&self) -> Box<Prop> {
+        Box::new(self.clone())
+    }
+
+    fn sight(&self) -> Option<usize> {
+        Some(MOUSE_SIGHT)
+    }
+
+    fn tick(&mut self, near: &Surroundings) -> Option<Direction> {
+        self.is_resting = !self.is_resting;
+        if self.is_resting {
+            return None;
+        }
+
+        let r = near.radius() as i32;
+        let mut heads = Vec::new();
+
+        for di in -r..r + 1 {
+            for dj in -r..r + 1 {
+                if let Kind::SnakeHead = near.get(di, dj).kind {
+                    heads.push((di, dj));
+                }
+            }
+        }
+
+        // how close the nearest head would be after moving to (i, j)
+        let closest = |(i, j): (i32, i32)| {
+            heads
+                .iter()
+                .map(|(hi, hj)| (hi - i).abs() + (hj - j).abs())
+                .min()
+        };
+
+        let here = match closest((0, 0)) {
+            Some(d) => d,
+            None => return None,
+        };
+
+        let dirs = [
+            (Direction::Up, (-1, 0)),
+            (Direction::Down, (1, 0)),
+            (Direction::Left, (0, -1)),
+            (Direction::Right, (0, 1)),
+        ];
+
+        dirs.iter()
+            .filter(|(_, (i, j))| match near.get(*i, *j).kind {
+                Kind::None => true,
+                _ => false,
+            })
+            .filter_map(|(dir, pos)| closest(*pos).map(|d| (*dir, d)))
+            .filter(|(_, d)| here < *d)
+            .max_by_key(|(_, d)| *d)
+            .map(|(dir, _)| dir)
+    }
+}
+
+// Worth a lot of score, and wanders around aimlessly.
+#[derive(Clone)]
+pub(crate) struct Bonus {
+    pub(crate) ticks: usize,
+}
+
+impl Prop for Bonus {
+    fn collision_result(&self) -> CollisionResult {
+        CollisionResult::RemoveSelf
+    }
+
+    fn collision_events(&self, id: SnakeID) -> Vec<SnakeEvent> {
+        vec![SnakeEvent {
+            id: id,
+            cmd: Cmd::GiveScore(5),
+        }]
+    }
+
+    fn get_timer(&self) -> Option<Timer> {
+        Some(Timer::new(30))
+    }
+
+    fn id(&self) -> ID {
+        5
+    }
+
+    fn box_clone(&self) -> Box<Prop> {
+        Box::new(self.clone())
+    }
+
+    fn sight(&self) -> Option<usize> {
+        Some(0)
+    }
+
+    fn tick(&mut self, _near: &Surroundings) -> Option<Direction> {
+        self.ticks += 1;
+
+        if self.ticks % 3 == 0 {
+            Some(Direction::rand())
+        } else {
+            None
         }
     }
 }
 
+// Covers 2x2 cells and is worth a lot, eating any of them eats it whole.
+#[derive(Clone)]
+pub(crate) struct GoldenApple;
+
+impl Prop for GoldenApple {
+    fn collision_result(&self) -> CollisionResult {
+        CollisionResult::RemoveSelf
+    }
+
+    fn collision_events(&self, id: SnakeID) -> Vec<SnakeEvent> {
+        vec![
+            SnakeEvent {
+                id: id,
+                cmd: Cmd::Grow(NonZeroUsize::new(3).unwrap()),
+            },
+            SnakeEvent {
+                id: id,
+                cmd: Cmd::GiveScore(10),
+            },
+        ]
+    }
+
+    fn get_timer(&self) -> Option<Timer> {
+        Some(Timer::new(40))
+    }
+
+    fn id(&self) -> ID {
+        6
+    }
+
+    fn box_clone(&self) -> Box<Prop> {
+        Box::new(self.clone())
+    }
+
+    fn shape(&self) -> Vec<Part> {
+        Part::rect(2, 2)
+    }
+}
+
 fn food_spawner() -> Box<Prop> {
-    let food: Vec<Box<Prop>> =
-        vec![Box::new(Food::GrowFood), Box::new(Food::BadFood)];
+    let food: Vec<(usize, fn() -> Box<Prop>)> = vec![
+        (3, || Box::new(Food::GrowFood)),
+        (1, || Box::new(Food::BadFood)),
+        (1, || Box::new(Food::FastFood)),
+        (1, || Box::new(Food::SlowFood)),
+        (1, || Box::new(Mouse { is_resting: false })),
+        (1, || Box::new(Bonus { ticks: 0 })),
+        (1, || Box::new(GoldenApple)),
+    ];
 
     let mut rng = rand::thread_rng();
-    food.into_iter().choose(&mut rng).unwrap()
+    let (_, spawn) = food.choose_weighted(&mut rng, |(w, _)| *w).unwrap();
+    spawn()
 }
 
 // ++++++++
 // + Grid +
 // ++++++++
 
+// Marks a cell that is not in `Grid::vacant`.
+const VACANT_NONE: usize = std::usize::MAX;
+
+// What a grid cell holds. Props live in `Grid::props` and are only
+// referenced here, together with their tag id so that `Grid::data` does
+// not have to look them up.
+#[derive(Debug, Clone, Copy)]
+enum Cell {
+    Vacant,
+    Prop(PropID, ID, Part),
+    SnakeBody(SnakeID),
+    SnakeHead(SnakeID),
+    ImmortalSnakeHead(SnakeID),
+}
+
+impl Cell {
+    fn is_vacant(&self) -> bool {
+        match *self {
+            Cell::Vacant => true,
+            _ => false,
+        }
+    }
+
+    fn owner(&self) -> Option<SnakeID> {
+        match *self {
+            Cell::SnakeBody(id)
+            | Cell::SnakeHead(id)
+            | Cell::ImmortalSnakeHead(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn tag(&self) -> Tag {
+        match *self {
+            Cell::Vacant => Tag {
+                kind: Kind::None,
+                id: 0,
+            },
+
+            Cell::Prop(_, id, part) => Tag {
+                kind: Kind::Prop(part),
+                id: id,
+            },
+
+            Cell::SnakeBody(id) => Tag {
+                kind: Kind::SnakeBody,
+                id: id,
+            },
+
+            Cell::SnakeHead(id) | Cell::ImmortalSnakeHead(id) => Tag {
+                kind: Kind::SnakeHead,
+                id: id,
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
 struct Grid {
-    grid: Vec<Vec<Option<Entity>>>,
+    rows: usize,
+    cols: usize,
+    // Row-major, cell (i, j) is at i * cols + j.
+    cells: Vec<Cell>,
+    props: HashMap<PropID, Box<Prop>>,
+    // The cells each prop covers. They are kept when a prop is taken out of
+    // the grid, so that it can be put back where it was.
+    placed: HashMap<PropID, Vec<(Index2D, Part)>>,
+    // Flat indices of every vacant cell, in no particular order, so that a
+    // uniformly random vacant cell can be picked in O(1).
+    vacant: Vec<usize>,
+    // For each cell, its position in `vacant` or `VACANT_NONE`.
+    slots: Vec<usize>,
+    // Snake cells that another snake's cell lies on top of, e.g. when a
+    // head passes over a teammate, by flat index and oldest first. They
+    // come back once what covers them is gone.
+    covered: HashMap<usize, Vec<Cell>>,
+    // Hazards lie below whatever else is in their cell, which is drawn as
+    // the hazard while it is vacant.
+    floor: Vec<Option<(HazardID, Phase)>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GridData {
     pub rows: u32,
     pub cols: u32,
-    pub tags: Vec<Vec<Tag>>,
+    // Where cell (0, 0) is in the whole grid, see GameData::window.
+    pub top: u32,
+    pub left: u32,
+    // Row-major, use `GridData::get` to look up a cell.
+    pub tags: Vec<Tag>,
+}
+
+impl GridData {
+    pub fn get(&self, i: usize, j: usize) -> Tag {
+        self.tags[i * self.cols as usize + j]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trail {
+    // The cells moved through, starting with the cell moved from and
+    // ending with the current one. Fast snakes move several cells per tick.
+    pub cells: Vec<(u32, u32)>,
+    // The move should be animated over `span` ticks, of which `step` have
+    // already passed. Slow snakes move once every `span` ticks.
+    pub span: u32,
+    pub step: u32,
+}
+
+impl Trail {
+    fn new(from: Index2D, to: Index2D, span: usize) -> Self {
+        Trail {
+            cells: vec![from.get_u32(), to.get_u32()],
+            span: span as u32,
+            step: 0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CameFrom {
-    Real(((u32, u32), (u32, u32))),
-    Dummy(((u32, u32), (u32, u32))),
+    Real(Trail),
+    Dummy(Trail),
+}
+
+impl CameFrom {
+    pub fn trail(&self) -> &Trail {
+        match self {
+            CameFrom::Real(t) | CameFrom::Dummy(t) => t,
+        }
+    }
+
+    fn trail_mut(&mut self) -> &mut Trail {
+        match self {
+            CameFrom::Real(t) | CameFrom::Dummy(t) => t,
+        }
+    }
+
+    // Records a one cell move, continuing the trail if the move follows
+    // one made earlier in the same tick.
+    fn push_real(
+        came_from: &mut Option<CameFrom>,
+        from: Index2D,
+        to: Index2D,
+        span: usize,
+    ) {
+        if let Some(CameFrom::Real(trail)) = came_from {
+            if trail.step == 0 && trail.cells.last() == Some(&from.get_u32()) {
+                trail.cells.push(to.get_u32());
+                return;
+            }
+        }
+
+        *came_from = Some(CameFrom::Real(Trail::new(from, to, span)));
+    }
+
+    // Called once per tick, drops moves that are done animating.
+    fn age(came_from: &mut Option<CameFrom>) {
+        let is_done = match came_from.as_mut().map(CameFrom::trail_mut) {
+            Some(trail) => {
+                trail.step += 1;
+                trail.span <= trail.step
+            }
+
+            None => false,
+        };
+
+        if is_done {
+            *came_from = None;
+        }
+    }
+}
+
+// What is needed to predict how a snake moves, see GameState::predicting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnakeCells {
+    // From the head to the tail.
+    pub cells: Vec<(u32, u32)>,
+    pub direction: Direction,
+    pub speed: Speed,
+    // how many cells the snake has yet to grow
+    pub grow: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameData {
     pub came_from_heads: HashMap<SnakeID, CameFrom>,
     pub came_from_tails: HashMap<SnakeID, CameFrom>,
+    pub came_from_props: HashMap<PropID, CameFrom>,
+    // the snakes that are alive and done spawning
+    pub snakes: HashMap<SnakeID, SnakeCells>,
+    // empty unless the game is played in teams
+    pub teams: HashMap<SnakeID, TeamID>,
+    pub scores: HashMap<SnakeID, u64>,
     pub grid_data: GridData,
 }
 
-impl Grid {
-    fn new(rows: usize, cols: usize) -> Self {
-        let mut grid = Vec::new();
+impl GameData {
+    // The part of the game within radius cells of center, which becomes
+    // the middle of the window. Everything is moved into the window's
+    // coordinates, wrapping around the edges of the grid, and trails that
+    // end outside of it are dropped. So are snakes with their head outside
+    // of it, the others are cut off where they leave it. Teams and scores
+    // are kept whole.
+    // Should only be called on the data of the whole grid.
+    pub fn window(&self, center: Index2D, radius: usize) -> GameData {
+        let rows = self.grid_data.rows as usize;
+        let cols = self.grid_data.cols as usize;
+        let height = (2 * radius + 1).min(rows);
+        let width = (2 * radius + 1).min(cols);
+
+        let (i, j) = center.get();
+        let top = (i + rows - height / 2) % rows;
+        let left = (j + cols - width / 2) % cols;
+
+        let mut tags = Vec::with_capacity(height * width);
+        for di in 0..height {
+            for dj in 0..width {
+                let i2 = (top + di) % rows;
+                let j2 = (left + dj) % cols;
+                tags.push(self.grid_data.tags[i2 * cols + j2]);
+            }
+        }
+
+        let to_window = |(i, j): &(u32, u32)| {
+            let i2 = (*i as usize + rows - top) % rows;
+            let j2 = (*j as usize + cols - left) % cols;
+            (i2 as u32, j2 as u32)
+        };
+
+        let window_came_from = |came_from: &CameFrom| {
+            let mut came_from = came_from.clone();
+            let trail = came_from.trail_mut();
+            trail.cells = trail.cells.iter().map(to_window).collect();
+
+            let (i, j) = *trail.cells.last().unwrap();
+            if (i as usize) < height && (j as usize) < width {
+                Some(came_from)
+            } else {
+                None
+            }
+        };
+
+        let window_all = |came_froms: &HashMap<u64, CameFrom>| {
+            came_froms
+                .iter()
+                .filter_map(|(id, x)| window_came_from(x).map(|x| (*id, x)))
+                .collect()
+        };
+
+        let is_inside = |(i, j): &(u32, u32)| {
+            (*i as usize) < height && (*j as usize) < width
+        };
+
+        let snakes = self
+            .snakes
+            .iter()
+            .filter_map(|(id, sn)| {
+                let cells: Vec<(u32, u32)> = sn
+                    .cells
+                    .iter()
+                    .map(to_window)
+                    .take_while(is_inside)
+                    .collect();
+
+                if cells.is_empty() {
+                    return None;
+                }
+
+                let sn = SnakeCells {
+                    cells: cells,
+                    direction: sn.direction,
+                    speed: sn.speed,
+                    grow: sn.grow,
+                };
+
+                Some((*id, sn))
+            })
+            .collect();
+
+        GameData {
+            came_from_heads: window_all(&self.came_from_heads),
+            came_from_tails: window_all(&self.came_from_tails),
+            came_from_props: window_all(&self.came_from_props),
+            snakes: snakes,
+            teams: self.teams.clone(),
+            scores: self.scores.clone(),
+            grid_data: GridData {
+                rows: height as u32,
+                cols: width as u32,
+                top: top as u32,
+                left: left as u32,
+                tags: tags,
+            },
+        }
+    }
+
+    // Replaces snake id with how it is in other, which should be in the
+    // same coordinates but may be larger, see GameState::predicting. What
+    // is outside of this window is dropped and cells that are taken by
+    // something else are left alone.
+    pub fn overlay(&mut self, other: &GameData, id: SnakeID) {
+        let is_snake = |tag: &Tag| match tag.kind {
+            Kind::SnakeHead | Kind::SnakeBody => tag.id == id,
+            _ => false,
+        };
+
+        let rows = self.grid_data.rows as usize;
+        let cols = self.grid_data.cols as usize;
+        let other_rows = other.grid_data.rows as usize;
+        let other_cols = other.grid_data.cols as usize;
+        let is_inside = |(i, j): &(u32, u32)| {
+            (*i as usize) < rows && (*j as usize) < cols
+        };
 
         for i in 0..rows {
-            grid.push(Vec::new());
+            for j in 0..cols {
+                let tag = &mut self.grid_data.tags[i * cols + j];
+
+                if is_snake(tag) {
+                    *tag = Tag {
+                        kind: Kind::None,
+                        id: 0,
+                    };
+                }
+
+                if other_rows <= i || other_cols <= j {
+                    continue;
+                }
+
+                let other_tag = other.grid_data.get(i, j);
+                if let Kind::None = tag.kind {
+                    if is_snake(&other_tag) {
+                        *tag = other_tag;
+                    }
+                }
+            }
+        }
+
+        // trails that end outside of the window are dropped
+        let shown = |x: &CameFrom| match x.trail().cells.last() {
+            Some(cell) if is_inside(cell) => Some(x.clone()),
+            _ => None,
+        };
+
+        self.came_from_heads.remove(&id);
+        self.came_from_tails.remove(&id);
+        self.snakes.remove(&id);
+
+        if let Some(x) = other.came_from_heads.get(&id).and_then(shown) {
+            self.came_from_heads.insert(id, x);
+        }
+        if let Some(x) = other.came_from_tails.get(&id).and_then(shown) {
+            self.came_from_tails.insert(id, x);
+        }
+        if let Some(x) = other.snakes.get(&id) {
+            let mut x = x.clone();
+            x.cells = x.cells.into_iter().take_while(is_inside).collect();
 
-            for _ in 0..cols {
-                grid[i].push(None);
+            if !x.cells.is_empty() {
+                self.snakes.insert(id, x);
             }
         }
+    }
+}
 
-        Grid { grid: grid }
+impl Grid {
+    fn new(rows: usize, cols: usize) -> Self {
+        // every cell starts out vacant
+        let vacant: Vec<usize> = (0..rows * cols).collect();
+        let slots: Vec<usize> = (0..rows * cols).collect();
+
+        Grid {
+            rows: rows,
+            cols: cols,
+            cells: vec![Cell::Vacant; rows * cols],
+            props: HashMap::new(),
+            placed: HashMap::new(),
+            vacant: vacant,
+            slots: slots,
+            covered: HashMap::new(),
+            floor: vec![None; rows * cols],
+        }
     }
 
     fn rows(&self) -> usize {
-        self.grid.len()
+        self.rows
     }
 
     fn cols(&self) -> usize {
-        self.grid[0].len()
+        self.cols
     }
 
-    fn add(&mut self, idx: Index2D, e: Entity) {
+    fn flat(&self, idx: Index2D) -> usize {
         let (i, j) = idx.get();
-        self.grid[i][j] = Some(e);
+        i * self.cols + j
+    }
+
+    fn mark_occupied(&mut self, k: usize) {
+        let slot = self.slots[k];
+
+        if slot == VACANT_NONE {
+            return;
+        }
+
+        // swap the last vacant cell into the freed slot
+        self.vacant.swap_remove(slot);
+        if let Some(&moved) = self.vacant.get(slot) {
+            self.slots[moved] = slot;
+        }
+
+        self.slots[k] = VACANT_NONE;
+    }
+
+    // Cells with a hazard never count as vacant, nothing spawns on them.
+    fn mark_vacant(&mut self, k: usize) {
+        if self.slots[k] != VACANT_NONE || self.floor[k].is_some() {
+            return;
+        }
+
+        self.slots[k] = self.vacant.len();
+        self.vacant.push(k);
+    }
+
+    fn add(&mut self, idx: Index2D, e: Entity) {
+        let k = self.flat(idx);
+
+        if let Cell::Prop(pid, _, _) = self.cells[k] {
+            self.remove_prop(pid);
+        }
+
+        self.cells[k] = match e {
+            Entity::Prop(pid, p) => return self.add_prop(idx, pid, p),
+            Entity::Hazard(id, phase) => {
+                return self.set_hazard(idx, id, phase);
+            }
+
+            Entity::SnakeBody(id) => Cell::SnakeBody(id),
+            Entity::SnakeHead(id) => Cell::SnakeHead(id),
+            Entity::ImmortalSnakeHead(id) => Cell::ImmortalSnakeHead(id),
+        };
+
+        self.mark_occupied(k);
     }
 
     fn remove(&mut self, idx: Index2D) {
-        let (i, j) = idx.get();
-        self.grid[i][j] = None
+        self.take(idx);
+    }
+
+    // Covers the prop's shape starting at idx, or the cells it was taken
+    // from. Cells that have been covered by something else since are left
+    // alone.
+    fn add_prop(&mut self, idx: Index2D, pid: PropID, p: Box<Prop>) {
+        let cells = match self.placed.remove(&pid) {
+            Some(cells) => cells,
+            None => self.shape_at(idx, &p.shape()),
+        };
+
+        for (idx2, part) in cells.iter() {
+            let k = self.flat(*idx2);
+
+            match self.cells[k] {
+                Cell::Vacant => (),
+                Cell::Prop(pid2, _, _) if pid2 == pid => (),
+                _ => continue,
+            }
+
+            self.cells[k] = Cell::Prop(pid, p.id(), *part);
+            self.mark_occupied(k);
+        }
+
+        self.placed.insert(pid, cells);
+        self.props.insert(pid, p);
     }
 
-    fn index_of_next_vacant(&self, idx: Index2D) -> Option<Index2D> {
-        let (start, end) = idx.get();
+    // Takes a prop out of the grid as a whole, clearing all of its cells.
+    fn take_prop(&mut self, pid: PropID) -> Option<Entity> {
+        let cells = self.placed.remove(&pid).unwrap_or_default();
+
+        for (idx, _) in cells {
+            let k = self.flat(idx);
 
-        for i in start..self.rows() {
-            for j in end..self.cols() {
-                if self.grid[i][j].is_none() {
-                    return Some(Index2D::new(i, j));
+            match self.cells[k] {
+                Cell::Prop(pid2, _, _) if pid2 == pid => {
+                    self.cells[k] = Cell::Vacant;
+                    self.mark_vacant(k);
                 }
+
+                _ => (),
             }
         }
 
-        for i in 0..self.rows() {
-            for j in 0..self.cols() {
-                if self.grid[i][j].is_none() {
-                    return Some(Index2D::new(i, j));
+        self.props.remove(&pid).map(|p| Entity::Prop(pid, p))
+    }
+
+    fn remove_prop(&mut self, pid: PropID) {
+        self.take_prop(pid);
+    }
+
+    fn shape_at(&self, idx: Index2D, shape: &[Part]) -> Vec<(Index2D, Part)> {
+        let (i, j) = idx.get();
+
+        shape
+            .iter()
+            .map(|part| {
+                let i2 = (i + part.di as usize) % self.rows;
+                let j2 = (j + part.dj as usize) % self.cols;
+                (Index2D::new(i2, j2), *part)
+            })
+            .collect()
+    }
+
+    // Whether a prop with the given shape can be placed at idx, cells that
+    // the prop pid already covers count as vacant. Props keep off hazards.
+    fn fits(&self, idx: Index2D, shape: &[Part], pid: PropID) -> bool {
+        self.shape_at(idx, shape).iter().all(|(idx2, _)| {
+            let k = self.flat(*idx2);
+
+            self.floor[k].is_none()
+                && match self.cells[k] {
+                    Cell::Vacant => true,
+                    Cell::Prop(pid2, _, _) => pid2 == pid,
+                    _ => false,
                 }
+        })
+    }
+
+    // Only clears the cell at idx, a prop taken this way keeps its other
+    // cells until it is put back or removed with remove_prop.
+    fn take(&mut self, idx: Index2D) -> Option<Entity> {
+        let k = self.flat(idx);
+        let cell = std::mem::replace(&mut self.cells[k], Cell::Vacant);
+        self.mark_vacant(k);
+
+        match cell {
+            Cell::Vacant => None,
+
+            Cell::Prop(pid, _, _) => match self.props.remove(&pid) {
+                Some(p) => Some(Entity::Prop(pid, p)),
+                None => panic!("error @ take: unknown prop id {}", pid),
+            },
+
+            Cell::SnakeBody(id) => Some(Entity::SnakeBody(id)),
+            Cell::SnakeHead(id) => Some(Entity::SnakeHead(id)),
+            Cell::ImmortalSnakeHead(id) => Some(Entity::ImmortalSnakeHead(id)),
+        }
+    }
+
+    fn prop(&self, pid: PropID) -> Option<&Prop> {
+        self.props.get(&pid).map(|p| p.as_ref())
+    }
+
+    fn prop_mut(&mut self, pid: PropID) -> Option<&mut Box<Prop>> {
+        self.props.get_mut(&pid)
+    }
+
+    fn tag(&self, idx: Index2D) -> Tag {
+        self.tag_of(self.flat(idx))
+    }
+
+    fn tag_of(&self, k: usize) -> Tag {
+        match (self.cells[k], self.floor[k]) {
+            (Cell::Vacant, Some((id, phase))) => Tag {
+                kind: Kind::Hazard(phase),
+                id: id,
+            },
+
+            (cell, _) => cell.tag(),
+        }
+    }
+
+    fn is_vacant(&self, idx: Index2D) -> bool {
+        let k = self.flat(idx);
+        self.cells[k].is_vacant() && self.floor[k].is_none()
+    }
+
+    // Unlike Grid::take, this leaves the hazard where it is.
+    fn hazard(&self, idx: Index2D) -> Option<Entity> {
+        self.floor[self.flat(idx)].map(|(id, phase)| Entity::Hazard(id, phase))
+    }
+
+    // Puts a hazard below the cell at idx, or changes its phase.
+    fn set_hazard(&mut self, idx: Index2D, id: HazardID, phase: Phase) {
+        let k = self.flat(idx);
+        self.floor[k] = Some((id, phase));
+        self.mark_occupied(k);
+    }
+
+    fn surroundings(&self, idx: Index2D, radius: usize) -> Surroundings {
+        let (i, j) = idx.get();
+        let (rows, cols) = (self.rows as i64, self.cols as i64);
+        let r = radius as i64;
+        let mut tags = Vec::with_capacity((2 * radius + 1).pow(2));
+
+        for di in -r..r + 1 {
+            for dj in -r..r + 1 {
+                let i2 = (i as i64 + di).rem_euclid(rows) as usize;
+                let j2 = (j as i64 + dj).rem_euclid(cols) as usize;
+                tags.push(self.tag_of(i2 * self.cols + j2));
             }
         }
 
-        None
+        Surroundings::new(radius, tags)
     }
 
-    fn index_of_rand_vacant(&self) -> Option<Index2D> {
-        let pos = Index2D::rand_in_range((0, self.rows()), (0, self.cols()));
-        self.index_of_next_vacant(pos)
+    // Moves the prop to to, see Grid::fits.
+    fn move_prop(&mut self, pid: PropID, to: Index2D) {
+        if let Some(en) = self.take_prop(pid) {
+            self.add(to, en);
+        }
     }
 
-    fn data(&self) -> GridData {
-        let mut tags = vec![Vec::with_capacity(self.cols()); self.rows()];
-        for i in 0 .. self.rows() {
-            for j in 0 .. self.cols() {
-                let x = self.grid[i][j]
-                    .as_ref()
-                    .map(|x| x.tag())
-                    .unwrap_or(Tag { kind: Kind::None, id: 0 });
+    // Puts a snake's cell on top of another snake's, which is kept below it,
+    // see Grid::covered.
+    fn cover(&mut self, idx: Index2D, e: Entity) {
+        let k = self.flat(idx);
+        let below = self.cells[k];
+
+        self.covered.entry(k).or_insert_with(Vec::new).push(below);
+        self.add(idx, e);
+    }
+
+    // Brings back the cell that was covered last, if the cell is vacant.
+    fn uncover(&mut self, k: usize) {
+        if !self.cells[k].is_vacant() {
+            return;
+        }
 
-                tags[i].push(x);
+        let below = match self.covered.get_mut(&k) {
+            Some(cells) => cells.pop(),
+            None => None,
+        };
+
+        if self.covered.get(&k).map_or(false, |cells| cells.is_empty()) {
+            self.covered.remove(&k);
+        }
+
+        if let Some(below) = below {
+            self.cells[k] = below;
+            self.mark_occupied(k);
+        }
+    }
+
+    // Snakes can lie on top of each other, e.g. when a head passes over a
+    // teammate. Snakes cover other snakes' cells, but leave any other
+    // entity alone.
+    fn add_owned(&mut self, idx: Index2D, id: SnakeID, e: Entity) {
+        let cell = self.cells[self.flat(idx)];
+
+        match cell.owner() {
+            _ if cell.is_vacant() => self.add(idx, e),
+            Some(owner) if owner == id => self.add(idx, e),
+            Some(_) => self.cover(idx, e),
+            None => (),
+        }
+    }
+
+    // Removes the snake's cell, wherever it is, uncovering what is below.
+    fn remove_owned(&mut self, idx: Index2D, id: SnakeID) {
+        let k = self.flat(idx);
+
+        if self.cells[k].owner() == Some(id) {
+            self.remove(idx);
+            self.uncover(k);
+        } else if let Some(cells) = self.covered.get_mut(&k) {
+            cells.retain(|cell| cell.owner() != Some(id));
+
+            if cells.is_empty() {
+                self.covered.remove(&k);
             }
         }
+    }
+
+    // Puts back what is left after a collision at idx, see Grid::take.
+    // Snakes cover other snakes.
+    fn put(&mut self, idx: Index2D, en: Option<Entity>) {
+        let k = self.flat(idx);
+
+        match en {
+            Some(en) => {
+                let is_snake = match en {
+                    Entity::SnakeBody(_)
+                    | Entity::SnakeHead(_)
+                    | Entity::ImmortalSnakeHead(_) => true,
+                    _ => false,
+                };
+
+                if is_snake && self.cells[k].owner().is_some() {
+                    self.cover(idx, en);
+                } else {
+                    self.add(idx, en);
+                }
+            }
+
+            None => {
+                self.remove(idx);
+                self.uncover(k);
+            }
+        }
+    }
+
+    fn index_of_rand_vacant(&self) -> Option<Index2D> {
+        let mut rng = rand::thread_rng();
+        let cols = self.cols;
+
+        self.vacant
+            .choose(&mut rng)
+            .map(|k| Index2D::new(k / cols, k % cols))
+    }
 
+    fn data(&self) -> GridData {
         GridData {
-            rows: self.rows() as u32,
-            cols: self.cols() as u32,
-            tags: tags,
+            rows: self.rows as u32,
+            cols: self.cols as u32,
+            top: 0,
+            left: 0,
+            tags: (0..self.cells.len()).map(|k| self.tag_of(k)).collect(),
         }
     }
 }
@@ -180,16 +1015,23 @@ impl Grid {
 // + Snake +
 // +++++++++
 
+#[derive(Clone)]
 struct Snake {
     id: SnakeID,
     pos: Index2D,
     prev_last: Index2D,
     is_dead: bool,
+    // removed from the game once its body has decayed
+    is_leaving: bool,
     death_timer: Option<Timer>,
     spawn_timer: Timer,
     score: usize,
     body: VecDeque<Index2D>,
     grow_count: Option<NonZeroUsize>,
+    speed: Speed,
+    speed_change: Option<(Speed, Timer)>,
+    move_ticks: usize,
+    frozen: Option<Timer>,
     curr_dir: Direction,
     next_dir: Option<Direction>,
     came_from_head: Option<CameFrom>,
@@ -197,17 +1039,23 @@ struct Snake {
 }
 
 impl Snake {
-    fn new(id: SnakeID, pos: Index2D) -> Self {
+    // The snake stays put for spawn_ticks ticks, unable to die.
+    fn new(id: SnakeID, pos: Index2D, spawn_ticks: usize) -> Self {
         let mut snake = Snake {
             id: id,
             pos: pos,
             prev_last: pos,
             is_dead: false,
+            is_leaving: false,
             death_timer: None,
-            spawn_timer: Timer::new(5),
+            spawn_timer: Timer::new(spawn_ticks),
             score: 0,
             body: VecDeque::new(),
             grow_count: None,
+            speed: Speed::normal(),
+            speed_change: None,
+            move_ticks: 0,
+            frozen: None,
             curr_dir: Direction::rand(),
             next_dir: None,
             came_from_head: None,
@@ -241,17 +1089,14 @@ impl Snake {
 
                     let first = self.body.pop_front();
                     first.map(|first| {
-                        grid.remove(first);
-
-                        let first = first.get_u32();
+                        grid.remove_owned(first, self.id);
 
                         if let Some(new_first) = self.body.front() {
-                            let x = CameFrom::Real((new_first.get_u32(), first));
-                            self.came_from_tail = Some(x);
+                            let x = Trail::new(first, *new_first, 1);
+                            self.came_from_tail = Some(CameFrom::Real(x));
                         } else {
-                            let prev_last = self.prev_last;
-                            let x = CameFrom::Dummy((prev_last.get_u32(), first));
-                            self.came_from_tail = Some(x);
+                            let x = Trail::new(first, self.prev_last, 1);
+                            self.came_from_tail = Some(CameFrom::Dummy(x));
                         }
                     });
                 }
@@ -261,6 +1106,63 @@ impl Snake {
         }
     }
 
+    fn age_came_from(&mut self) {
+        CameFrom::age(&mut self.came_from_head);
+        CameFrom::age(&mut self.came_from_tail);
+    }
+
+    fn speed(&self) -> Speed {
+        self.speed_change
+            .as_ref()
+            .map(|(speed, _)| *speed)
+            .unwrap_or(self.speed)
+    }
+
+    fn change_speed(&mut self, speed: Speed, ticks: NonZeroUsize) {
+        self.speed_change = Some((speed, Timer::new(ticks.get())));
+        self.move_ticks = 0;
+    }
+
+    // Number of cells to move this tick, should be called once per tick.
+    fn moves_this_tick(&mut self) -> usize {
+        // a spawning snake stays put, but still needs to place its head
+        if !self.spawn_timer.is_done() {
+            return 1;
+        }
+
+        if let Some(timer) = self.frozen.as_mut() {
+            timer.tick();
+            if timer.is_done() {
+                self.frozen = None;
+            }
+
+            return 0;
+        }
+
+        let speed = self.speed();
+
+        let is_done = match self.speed_change.as_mut() {
+            Some((_, timer)) => {
+                timer.tick();
+                timer.is_done()
+            }
+
+            None => false,
+        };
+
+        if is_done {
+            self.speed_change = None;
+        }
+
+        self.move_ticks += 1;
+        if self.move_ticks < speed.ticks_per_move() {
+            return 0;
+        }
+
+        self.move_ticks = 0;
+        speed.cells_per_tick()
+    }
+
     fn should_spawn(&self) -> bool {
         self.death_timer
             .as_ref()
@@ -274,7 +1176,7 @@ impl Snake {
 
     fn remove_head(&mut self, grid: &mut Grid) {
         if self.spawn_timer.is_done() {
-            grid.remove(self.pos);
+            grid.remove_owned(self.pos, self.id);
         }
     }
 
@@ -283,7 +1185,7 @@ impl Snake {
             return;
         }
 
-        let prev_pos = self.pos.get_u32();
+        let prev_pos = self.pos;
 
         // move head
         self.tick_dir();
@@ -293,32 +1195,34 @@ impl Snake {
             .wrap((0, grid.rows()), (0, grid.cols()));
 
         // update came_from
-        let x = CameFrom::Real((self.pos.get_u32(), prev_pos));
-        self.came_from_head = Some(x);
+        let span = self.speed().ticks_per_move();
+        CameFrom::push_real(&mut self.came_from_head, prev_pos, self.pos, span);
     }
 
     fn move_body(&mut self, grid: &mut Grid) {
         if !self.spawn_timer.is_done() {
-            grid.add(self.pos, Entity::ImmortalSnakeHead(self.id));
-            let x = CameFrom::Real((self.pos.get_u32(), self.pos.get_u32()));
-            self.came_from_head = Some(x);
+            let head = Entity::ImmortalSnakeHead(self.id);
+            grid.add_owned(self.pos, self.id, head);
+            let x = Trail::new(self.pos, self.pos, 1);
+            self.came_from_head = Some(CameFrom::Real(x));
             return;
         }
 
         // move body
         self.body.push_front(self.pos);
-        grid.add(self.pos, Entity::SnakeBody(self.id));
+        grid.add_owned(self.pos, self.id, Entity::SnakeBody(self.id));
 
         self.grow_count = match self.grow_count.take() {
             Some(x) => x.decr(1),
 
             None => {
                 let last = self.body.pop_back().unwrap();
-                grid.remove(last);
+                grid.remove_owned(last, self.id);
 
                 if let Some(new_last) = self.body.back() {
-                    let x = CameFrom::Real((new_last.get_u32(), last.get_u32()));
-                    self.came_from_tail = Some(x);
+                    let span = self.speed().ticks_per_move();
+                    let came_from = &mut self.came_from_tail;
+                    CameFrom::push_real(came_from, last, *new_last, span);
                 }
 
                 // update dummy tail
@@ -336,20 +1240,172 @@ impl Snake {
         };
     }
 
-    fn kill(&mut self) {
+    // Gives up the last body cell, returns false if there is only one.
+    fn shed_tail(&mut self, grid: &mut Grid) -> bool {
+        if self.body.len() <= 1 {
+            return false;
+        }
+
+        let last = self.body.pop_back().unwrap();
+        grid.remove_owned(last, self.id);
+
+        if let Some(new_last) = self.body.back() {
+            let x = Trail::new(last, *new_last, 1);
+            self.came_from_tail = Some(CameFrom::Real(x));
+        }
+
+        self.prev_last = last;
+        true
+    }
+
+    // Cuts up to n cells off the tail, keeping at least one.
+    fn shrink(&mut self, n: NonZeroUsize, grid: &mut Grid) {
+        let mut cut = Vec::new();
+
+        for _ in 0..n.get() {
+            if self.body.len() <= 1 {
+                break;
+            }
+
+            let last = self.body.pop_back().unwrap();
+            grid.remove_owned(last, self.id);
+            cut.push(last);
+        }
+
+        if let (Some(last), Some(new_last)) = (cut.last(), self.body.back()) {
+            // the tail is animated back over every cell that was cut
+            let mut cells: Vec<(u32, u32)> =
+                cut.iter().map(Index2D::get_u32).collect();
+            cells.push(new_last.get_u32());
+
+            self.came_from_tail = Some(CameFrom::Real(Trail {
+                cells: cells,
+                span: 1,
+                step: 0,
+            }));
+            self.prev_last = *last;
+        }
+    }
+
+    fn reverse(&mut self, grid: &mut Grid) {
+        let old_pos = self.pos;
+
+        let new_pos = match self.body.pop_back() {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        // the new head looks away from the cell it was attached to
+        let next = self.body.back().cloned().unwrap_or(old_pos);
+        let dirs = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ];
+
+        let (rows, cols) = ((0, grid.rows()), (0, grid.cols()));
+        let dir = dirs
+            .iter()
+            .find(|dir| new_pos.neighbor(dir).wrap(rows, cols) == next)
+            .map(Direction::opposite)
+            .unwrap_or(self.curr_dir);
+
+        grid.remove_owned(old_pos, self.id);
+        grid.remove_owned(new_pos, self.id);
+
+        self.body = self.body.drain(..).rev().collect();
+        self.body.push_back(old_pos);
+        grid.add_owned(old_pos, self.id, Entity::SnakeBody(self.id));
+
+        self.pos = new_pos;
+        grid.add_owned(new_pos, self.id, Entity::SnakeHead(self.id));
+
+        self.curr_dir = dir;
+        self.next_dir = None;
+        self.prev_last = old_pos;
+
+        let x = Trail::new(new_pos, new_pos, 1);
+        self.came_from_head = Some(CameFrom::Real(x));
+        self.came_from_tail = None;
+    }
+
+    fn teleport(&mut self, idx: Index2D, grid: &mut Grid) {
+        let (i, j) = idx.get();
+
+        if grid.rows() <= i || grid.cols() <= j || !grid.is_vacant(idx) {
+            return;
+        }
+
+        grid.remove_owned(self.pos, self.id);
+        self.pos = idx;
+        grid.add_owned(idx, self.id, Entity::SnakeHead(self.id));
+
+        // no trail, the head should not slide across the grid
+        let x = Trail::new(idx, idx, 1);
+        self.came_from_head = Some(CameFrom::Real(x));
+    }
+
+    // Cuts the body at idx, returns the cells behind it that were cut off.
+    // The cell at idx itself is left to whoever cut the body.
+    fn sever(&mut self, idx: Index2D, grid: &mut Grid) -> Vec<Index2D> {
+        let k = match self.body.iter().position(|x| *x == idx) {
+            Some(k) => k,
+            None => return Vec::new(),
+        };
+
+        let cut: Vec<Index2D> =
+            self.body.split_off(k).into_iter().skip(1).collect();
+        for x in cut.iter() {
+            grid.remove_owned(*x, self.id);
+        }
+
+        self.came_from_tail = None;
+        self.prev_last = idx;
+        cut
+    }
+
+    fn freeze(&mut self, ticks: NonZeroUsize) {
+        self.frozen = Some(Timer::new(ticks.get()));
+    }
+
+    // A snake that is done spawning, the body goes from the neck to the tail.
+    fn placed(
+        id: SnakeID,
+        pos: Index2D,
+        dir: Direction,
+        body: Vec<Index2D>,
+    ) -> Self {
+        let mut snake = Snake::new(id, pos, 0);
+        snake.grow_count = None;
+        snake.curr_dir = dir;
+        snake.prev_last = *body.last().unwrap_or(&pos);
+        snake.body = body.into_iter().collect();
+        snake
+    }
+
+    // The snake respawns death_ticks ticks after its body has decayed.
+    fn kill(&mut self, death_ticks: usize) {
         self.is_dead = true;
         //  1 + is for the dummy tail
-        self.death_timer = Some(Timer::new(1 + self.body.len()));
+        let ticks = 1 + self.body.len() + death_ticks;
+        self.death_timer = Some(Timer::new(ticks));
     }
 
     fn remove(&mut self, grid: &mut Grid) {
         for idx in self.body.iter() {
-            grid.remove(*idx);
+            grid.remove_owned(*idx, self.id);
         }
 
         self.body.clear();
     }
 
+    // Like remove, but also clears the head of a snake that is alive.
+    fn remove_all(&mut self, grid: &mut Grid) {
+        grid.remove_owned(self.pos, self.id);
+        self.remove(grid);
+    }
+
     fn give_direction(&mut self, dir: Direction) {
         if self.spawn_timer.is_done() {
             self.next_dir = Some(dir);
@@ -381,6 +1437,7 @@ impl Snake {
 // + PropManager +
 // +++++++++++++++
 
+#[derive(Clone)]
 struct PropManager {
     props: Vec<(Option<Timer>, PropID, Index2D)>,
 }
@@ -406,28 +1463,212 @@ impl PropManager {
         self.props.retain(|(_, pid2, _)| pid != *pid2)
     }
 
-    fn indices_to_remove(&mut self) -> Vec<Index2D> {
+    fn ids_to_remove(&mut self) -> Vec<PropID> {
         self.props
             .drain_filter(|(opt, _, _)| match opt {
                 None => false,
                 Some(timer) => timer.is_done(),
             })
-            .map(|(_, _, idx)| idx)
+            .map(|(_, pid, _)| pid)
             .collect()
     }
 }
 
+// +++++++++++++++++
+// + HazardManager +
+// +++++++++++++++++
+
+// Hazards never move or expire, they only cycle through their phases.
+#[derive(Clone)]
+struct HazardManager {
+    hazards: Vec<(HazardID, Index2D, Cycle)>,
+    ticks: usize,
+}
+
+impl HazardManager {
+    fn new() -> Self {
+        HazardManager {
+            hazards: Vec::new(),
+            ticks: 0,
+        }
+    }
+
+    fn add(&mut self, grid: &mut Grid, id: HazardID, idx: Index2D, c: Cycle) {
+        grid.set_hazard(idx, id, c.phase_at(self.ticks));
+        self.hazards.push((id, idx, c));
+    }
+
+    // Returns the cells that just turned lethal.
+    fn tick(&mut self, grid: &mut Grid) -> Vec<Index2D> {
+        self.ticks += 1;
+        let mut lethal = Vec::new();
+
+        for (id, idx, cycle) in self.hazards.iter() {
+            let phase = cycle.phase_at(self.ticks);
+
+            if phase != cycle.phase_at(self.ticks - 1) {
+                grid.set_hazard(*idx, *id, phase);
+
+                if phase == Phase::Lethal {
+                    lethal.push(*idx);
+                }
+            }
+        }
+
+        lethal
+    }
+}
+
+// +++++++++++++++++++++++++
+// + SnakeView & PropView +
+// +++++++++++++++++++++++++
+
+// Read-only views of what is inside a GameState, see GameState::snakes and
+// GameState::props.
+
+#[derive(Clone, Copy)]
+pub struct SnakeView<'a> {
+    snake: &'a Snake,
+    team: Option<TeamID>,
+}
+
+impl<'a> SnakeView<'a> {
+    pub fn id(&self) -> SnakeID {
+        self.snake.id
+    }
+
+    pub fn team(&self) -> Option<TeamID> {
+        self.team
+    }
+
+    pub fn head(&self) -> Index2D {
+        self.snake.pos
+    }
+
+    // From the cell next to the head to the tail.
+    pub fn body(&self) -> impl Iterator<Item = Index2D> + 'a {
+        self.snake.body.iter().cloned()
+    }
+
+    // Head included.
+    pub fn len(&self) -> usize {
+        1 + self.snake.body.len()
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.snake.curr_dir
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.snake.speed()
+    }
+
+    pub fn score(&self) -> u64 {
+        self.snake.score as u64
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.snake.is_dead
+    }
+
+    // Spawning snakes stand still and can not be killed.
+    pub fn is_spawning(&self) -> bool {
+        !self.snake.spawn_timer.is_done()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct PropView<'a> {
+    id: PropID,
+    pos: Index2D,
+    timer: &'a Option<Timer>,
+    prop: &'a Prop,
+}
+
+impl<'a> PropView<'a> {
+    pub fn id(&self) -> PropID {
+        self.id
+    }
+
+    pub fn pos(&self) -> Index2D {
+        self.pos
+    }
+
+    // The same id as in the prop's Tag.
+    pub fn kind(&self) -> ID {
+        self.prop.id()
+    }
+
+    // Relative to pos, see Prop::shape.
+    pub fn shape(&self) -> Vec<Part> {
+        self.prop.shape()
+    }
+
+    // None for props that never expire.
+    pub fn ticks_left(&self) -> Option<usize> {
+        self.timer.as_ref().map(|timer| timer.remaining())
+    }
+}
+
 // +++++++++++++
 // + GameState +
 // +++++++++++++
 
+// What happened during the last tick, see GameState::events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GameEvent {
+    Died(SnakeID),
+    // the id is the same as in the eaten prop's Tag
+    Ate(SnakeID, ID),
+    Respawned(SnakeID),
+    // a removed snake is gone for good
+    Left(SnakeID),
+}
+
+// how long a boost from GameState::boost lasts
+const BOOST_TICKS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    // nobody ever wins
+    Endless,
+    // the first team to reach the score wins
+    ScoreLimit(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TailCutting {
+    // running into another snake's body is lethal
+    Off,
+    // the part of the body that was cut off disappears
+    Vanish,
+    // the part of the body that was cut off turns into food
+    Food,
+}
+
+#[derive(Clone)]
 pub struct GameState {
     grid: Grid,
     can_get_game_data: bool,
+    is_paused: bool,
+    // how many times the game has advanced
+    ticks: u64,
+    events: Vec<GameEvent>,
     prop_manager: PropManager,
+    hazard_manager: HazardManager,
+    came_from_props: HashMap<PropID, CameFrom>,
     // prop_spawner: Box<Fn() -> Box<Prop>>,
     prop_spawn_timer: Timer,
+    spawn_ticks: usize,
+    death_ticks: usize,
     snakes: HashMap<SnakeID, Snake>,
+    teams: HashMap<SnakeID, TeamID>,
+    team_count: usize,
+    friendly_fire: bool,
+    mode: GameMode,
+    tail_cutting: TailCutting,
+    decay_on_remove: bool,
+    hazard_ids: std::ops::Range<u64>,
     prop_ids: std::ops::Range<u64>,
     snake_ids: std::ops::Range<u64>,
 }
@@ -437,6 +1678,14 @@ pub struct GameStateBuilder {
     cols: usize,
     // prop_spawner: Box<Fn() -> Box<Prop> + Send>,
     prop_spawn_timer: Timer,
+    spawn_ticks: usize,
+    death_ticks: usize,
+    team_count: usize,
+    friendly_fire: bool,
+    mode: GameMode,
+    tail_cutting: TailCutting,
+    decay_on_remove: bool,
+    map: Option<Map>,
 }
 
 impl GameStateBuilder {
@@ -446,9 +1695,47 @@ impl GameStateBuilder {
             cols: 16,
             // prop_spawner: Box::new(food_spawner),
             prop_spawn_timer: Timer::new(5),
+            spawn_ticks: 5,
+            death_ticks: 0,
+            team_count: 0,
+            friendly_fire: true,
+            mode: GameMode::Endless,
+            tail_cutting: TailCutting::Off,
+            decay_on_remove: false,
+            map: None,
         }
     }
 
+    // With 0 teams, the default, every snake plays for itself.
+    pub fn with_teams(mut self, n: usize) -> Self {
+        self.team_count = n;
+        self
+    }
+
+    // Whether running into a teammate's body is lethal, it is by default.
+    pub fn with_friendly_fire(mut self, on: bool) -> Self {
+        self.friendly_fire = on;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: GameMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // Whether running into another snake's body severs it instead, see
+    // TailCutting.
+    pub fn with_tail_cutting(mut self, tail_cutting: TailCutting) -> Self {
+        self.tail_cutting = tail_cutting;
+        self
+    }
+
+    // Whether removed snakes decay like dead ones instead of vanishing.
+    pub fn with_decay_on_remove(mut self, on: bool) -> Self {
+        self.decay_on_remove = on;
+        self
+    }
+
     pub fn with_prop_spawner(mut self, f: Box<Fn() -> Box<Prop> + Send>) -> Self {
         // self.prop_spawner = f;
         self
@@ -459,20 +1746,58 @@ impl GameStateBuilder {
         self
     }
 
+    // How long new snakes stay put, unable to die. 5 ticks by default.
+    pub fn with_spawn_ticks(mut self, ticks: usize) -> Self {
+        self.spawn_ticks = ticks;
+        self
+    }
+
+    // How long dead snakes wait to respawn once their body has decayed,
+    // they respawn at once by default.
+    pub fn with_death_ticks(mut self, ticks: usize) -> Self {
+        self.death_ticks = ticks;
+        self
+    }
+
     pub fn with_dimensions(mut self, rows: usize, cols: usize) -> Self {
         self.rows = rows;
         self.cols = cols;
         self
     }
 
+    // Overrides the dimensions with those of the map.
+    pub fn with_map(mut self, map: Map) -> Self {
+        self.rows = map.rows;
+        self.cols = map.cols;
+        self.map = Some(map);
+        self
+    }
+
     pub fn build(self) -> GameState {
-        GameState {
+        let mut state = GameState {
             grid: Grid::new(self.rows, self.cols),
             can_get_game_data: false,
+            is_paused: false,
+            ticks: 0,
+            events: Vec::new(),
             prop_manager: PropManager::new(),
+            hazard_manager: HazardManager::new(),
+            came_from_props: HashMap::new(),
             // prop_spawner: self.prop_spawner,
             prop_spawn_timer: self.prop_spawn_timer,
+            spawn_ticks: self.spawn_ticks,
+            death_ticks: self.death_ticks,
             snakes: HashMap::new(),
+            teams: HashMap::new(),
+            team_count: self.team_count,
+            friendly_fire: self.friendly_fire,
+            mode: self.mode,
+            tail_cutting: self.tail_cutting,
+            decay_on_remove: self.decay_on_remove,
+            hazard_ids: std::ops::Range {
+                start: 0,
+                end: std::u64::MAX,
+            },
             prop_ids: std::ops::Range {
                 start: 0,
                 end: std::u64::MAX,
@@ -481,7 +1806,17 @@ impl GameStateBuilder {
                 start: 0,
                 end: std::u64::MAX,
             },
+        };
+
+        if let Some(map) = self.map {
+            for (idx, cycle) in map.hazards {
+                let id = state.hazard_ids.next().unwrap();
+                let grid = &mut state.grid;
+                state.hazard_manager.add(grid, id, idx, cycle);
+            }
         }
+
+        state
     }
 }
 
@@ -494,16 +1829,13 @@ impl GameState {
         self.snake_ids.next().unwrap()
     }
 
-    fn spawn_snake(grid: &Grid, id: SnakeID) -> Option<Snake> {
-        grid.index_of_rand_vacant().map(|idx| Snake::new(id, idx))
-    }
-
-    fn snake_by_id(&mut self, id: SnakeID) -> &mut Snake {
-        if let Some(sn) = self.snakes.get_mut(&id) {
-            return sn;
-        }
-
-        panic!("error @ snake_by_id: unknown id {}", id)
+    fn spawn_snake(
+        grid: &Grid,
+        id: SnakeID,
+        spawn_ticks: usize,
+    ) -> Option<Snake> {
+        grid.index_of_rand_vacant()
+            .map(|idx| Snake::new(id, idx, spawn_ticks))
     }
 
     fn tick_prop_spawn_timer(&mut self) {
@@ -512,8 +1844,11 @@ impl GameState {
                 let pid = self.next_prop_id();
                 // let prop = (self.prop_spawner)();
                 let prop = food_spawner();
-                self.prop_manager.add(prop.get_timer(), pid, pos);
-                self.grid.add(pos, Entity::Prop(pid, prop));
+
+                // props that do not fit are skipped until the next spawn
+                if self.grid.fits(pos, &prop.shape(), pid) {
+                    self.add_prop(pid, pos, prop);
+                }
             }
 
             self.prop_spawn_timer.reset();
@@ -522,117 +1857,492 @@ impl GameState {
         self.prop_spawn_timer.tick();
     }
 
+    fn add_prop(&mut self, pid: PropID, pos: Index2D, prop: Box<Prop>) {
+        self.prop_manager.add(prop.get_timer(), pid, pos);
+        self.grid.add(pos, Entity::Prop(pid, prop));
+    }
+
     fn tick_prop_manager(&mut self) {
         self.prop_manager.tick();
-        let idcs = self.prop_manager.indices_to_remove();
-        for idx in idcs {
-            self.grid.remove(idx);
+        let pids = self.prop_manager.ids_to_remove();
+        for pid in pids {
+            self.grid.remove_prop(pid);
+        }
+    }
+
+    fn move_props(&mut self) {
+        let grid = &mut self.grid;
+
+        for (_, pid, idx) in self.prop_manager.props.iter_mut() {
+            let sight = grid.prop(*pid).and_then(|p| p.sight());
+            let near = match sight {
+                Some(r) => grid.surroundings(*idx, r),
+                None => continue,
+            };
+
+            let dir = grid.prop_mut(*pid).and_then(|p| p.tick(&near));
+            let shape = match grid.prop(*pid) {
+                Some(p) => p.shape(),
+                None => continue,
+            };
+
+            let to = match dir {
+                Some(dir) => {
+                    idx.neighbor(&dir).wrap((0, grid.rows()), (0, grid.cols()))
+                }
+                None => continue,
+            };
+
+            if grid.fits(to, &shape, *pid) {
+                grid.move_prop(*pid, to);
+                let x = CameFrom::Real(Trail::new(*idx, to, 1));
+                self.came_from_props.insert(*pid, x);
+                *idx = to;
+            }
+        }
+    }
+
+    // Kills the snakes whose heads are on hazards that just turned lethal,
+    // the others only kill snakes moving onto them.
+    fn tick_hazards(&mut self) {
+        let lethal = self.hazard_manager.tick(&mut self.grid);
+
+        for sn in self.snakes.values_mut() {
+            if sn.is_mortal() && lethal.contains(&sn.pos) {
+                sn.kill(self.death_ticks);
+                self.events.push(GameEvent::Died(sn.id));
+            }
         }
     }
 
     fn tick_props(&mut self) {
+        self.came_from_props.clear();
         self.tick_prop_spawn_timer();
         self.tick_prop_manager();
+        self.move_props();
     }
 
-    pub fn tick(&mut self) {
+    // Moves every snake that has moves left one cell and resolves the
+    // resulting collisions.
+    fn move_snakes(&mut self, moves: &mut HashMap<SnakeID, usize>) {
+        let mut moved = Vec::new();
+
         for sn in self.snakes.values_mut() {
-            sn.tick(&mut self.grid);
+            let n = moves.get_mut(&sn.id);
+
+            match n {
+                Some(n) if 0 < *n && !sn.is_dead => {
+                    *n -= 1;
+                    sn.remove_head(&mut self.grid);
+                    sn.move_body(&mut self.grid);
+                    sn.tick_head(&mut self.grid);
+                    moved.push(sn.id);
+                }
 
-            if !sn.is_dead {
-                sn.remove_head(&mut self.grid);
-                sn.move_body(&mut self.grid);
-                sn.tick_head(&mut self.grid);
+                _ => (),
             }
         }
 
         let mut collisions: HashMap<(usize, usize), Vec<Entity>> =
             HashMap::new();
 
-        for sn in self.snakes.values().filter(|sn| sn.is_mortal()) {
-            let pos = sn.pos.get();
-            let en = Entity::SnakeHead(sn.id);
-
-            if collisions.contains_key(&pos) {
-                collisions.get_mut(&pos).unwrap().push(en);
-            } else {
-                let mut ens = vec![en];
-                let (i, j) = pos;
+        for id in moved {
+            let sn = &self.snakes[&id];
 
-                self.grid.grid[i][j].take().map(|en2| ens.push(en2));
-                collisions.insert(pos, ens);
+            if !sn.is_mortal() {
+                continue;
             }
+
+            let en = Entity::SnakeHead(sn.id);
+            collisions.entry(sn.pos.get()).or_insert_with(Vec::new).push(en);
         }
 
-        let mut prop_evs = Vec::new();
         let mut snake_evs = Vec::new();
 
-        for ((i, j), ens) in collisions.drain() {
-            let (en, prop_evs2, snake_evs2) = Entity::collide_many(ens);
-            self.grid.grid[i][j] = en;
-            prop_evs.extend(prop_evs2);
+        // Cells are resolved one at a time, so that a prop covering several
+        // of them is gone before the next head runs into it.
+        for ((i, j), mut ens) in collisions.drain() {
+            let idx = Index2D::new(i, j);
+            let below = self.grid.take(idx);
+
+            // the body a head runs into, if any, stays below a head that
+            // survives
+            let body = match below {
+                Some(Entity::SnakeBody(id)) => Some(id),
+                _ => None,
+            };
+            below.map(|en2| ens.push(en2));
+            self.grid.hazard(idx).map(|en2| ens.push(en2));
+
+            // a prop is only eaten by a head that runs into it alone
+            let eaten = match ens.as_slice() {
+                [Entity::SnakeHead(id), Entity::Prop(pid, p)] => {
+                    Some((*id, *pid, p.id()))
+                }
+                _ => None,
+            };
+
+            let rules = Rules {
+                friendly_fire: self.friendly_fire,
+                tail_cutting: self.tail_cutting != TailCutting::Off,
+                teams: &self.teams,
+            };
+
+            let (en, prop_evs, snake_evs2) =
+                Entity::collide_many(ens, idx, &rules);
+
+            if let (Some(id), Some(Entity::SnakeHead(_))) = (body, &en) {
+                self.grid.add(idx, Entity::SnakeBody(id));
+            }
+            self.grid.put(idx, en);
+
+            if let Some((id, pid, kind)) = eaten {
+                if prop_evs.contains(&PropEvent::Remove(pid)) {
+                    self.events.push(GameEvent::Ate(id, kind));
+                }
+            }
+
+            self.process_prop_events(prop_evs);
             snake_evs.extend(snake_evs2);
         }
 
-        self.process_prop_events(prop_evs);
         self.process_snake_events(snake_evs);
+    }
+
+    // Does nothing while the game is paused, see GameState::step.
+    pub fn tick(&mut self) {
+        if !self.is_paused {
+            self.advance();
+        }
+    }
+
+    // Advances the game one tick, whether it is paused or not.
+    pub fn step(&mut self) {
+        self.advance();
+    }
+
+    // Freezes the game, timers included, until it is resumed.
+    pub fn pause(&mut self) {
+        self.is_paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.is_paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    // Ticks while paused do not count, steps do.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    // Everything that happened during the last tick, in no particular
+    // order.
+    pub fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    // Puts a snake that is done spawning on the grid, for building games
+    // from pictures, see scenario::Scenario. With teams, snakes take turns
+    // joining them.
+    pub(crate) fn place_snake(
+        &mut self,
+        pos: Index2D,
+        dir: Direction,
+        body: Vec<Index2D>,
+    ) -> SnakeID {
+        let id = self.next_snake_id();
+        self.insert_snake(id, pos, dir, body);
+
+        if 0 < self.team_count {
+            self.teams.insert(id, id % self.team_count as TeamID);
+        }
+
+        id
+    }
+
+    fn insert_snake(
+        &mut self,
+        id: SnakeID,
+        pos: Index2D,
+        dir: Direction,
+        body: Vec<Index2D>,
+    ) {
+        self.grid.add(pos, Entity::SnakeHead(id));
+        for idx in body.iter() {
+            self.grid.add(*idx, Entity::SnakeBody(id));
+        }
+
+        self.snakes.insert(id, Snake::placed(id, pos, dir, body));
+    }
+
+    // A game of the map's size with nothing but snake id in it, for clients
+    // predicting how their snake moves before the server tells them. It
+    // keeps the coordinates of gd, which may be a window, so the snake
+    // wraps around where the map does. None if the snake is not in gd.
+    // Speed changes last as long as the game.
+    pub fn predicting(
+        gd: &GameData,
+        id: SnakeID,
+        rows: usize,
+        cols: usize,
+    ) -> Option<GameState> {
+        let data = gd.snakes.get(&id)?;
+
+        let mut st = GameState::builder()
+            .with_dimensions(rows, cols)
+            .with_prop_spawn_timer(Timer::new(std::usize::MAX))
+            .build();
+
+        let mut cells = data
+            .cells
+            .iter()
+            .map(|(i, j)| Index2D::new(*i as usize, *j as usize));
+
+        let head = cells.next()?;
+        st.insert_snake(id, head, data.direction, cells.collect());
+
+        let sn = st.snakes.get_mut(&id).unwrap();
+        sn.speed = data.speed;
+        sn.grow_count = NonZeroUsize::new(data.grow as usize);
+
+        Some(st)
+    }
+
+    pub(crate) fn place_prop(&mut self, pos: Index2D, prop: Box<Prop>) {
+        let pid = self.next_prop_id();
+        self.add_prop(pid, pos, prop);
+    }
+
+    fn advance(&mut self) {
+        self.events.clear();
+        self.tick_hazards();
+
+        let mut moves = HashMap::new();
+
+        for sn in self.snakes.values_mut() {
+            sn.age_came_from();
+            sn.tick(&mut self.grid);
+
+            if !sn.is_dead {
+                moves.insert(sn.id, sn.moves_this_tick());
+            }
+        }
+
+        // Fast snakes move several cells per tick, one cell at a time so
+        // that nothing is skipped over without colliding.
+        let steps = moves.values().cloned().max().unwrap_or(0);
+        for _ in 0..steps {
+            self.move_snakes(&mut moves);
+        }
+
+        let mut left = Vec::new();
 
         for snake in self.snakes.values_mut() {
-            if snake.is_dead && snake.should_spawn() {
+            if snake.is_leaving && snake.should_spawn() {
                 snake.remove(&mut self.grid);
-                GameState::spawn_snake(&self.grid, snake.id).map(|mut snake2| {
+                left.push(snake.id);
+            } else if snake.is_dead && snake.should_spawn() {
+                snake.remove(&mut self.grid);
+                let spawned = GameState::spawn_snake(
+                    &self.grid,
+                    snake.id,
+                    self.spawn_ticks,
+                );
+
+                if let Some(mut snake2) = spawned {
                     snake2.score = snake.score;
                     *snake = snake2;
-                });
+                    self.events.push(GameEvent::Respawned(snake.id));
+                }
+
                 snake.move_body(&mut self.grid);
             }
         }
 
+        for id in left {
+            self.snakes.remove(&id);
+            self.teams.remove(&id);
+            self.events.push(GameEvent::Left(id));
+        }
+
         // tick_props() uses grid, so it needs to be called when grid is in
         // a consistant state. This is a good place to do it, just after
         // we handle collisions and remove snakes.
         self.tick_props();
 
+        self.ticks += 1;
         self.can_get_game_data = true;
     }
 
     fn process_prop_events(&mut self, evs: Vec<PropEvent>) {
         for ev in evs {
             match ev {
-                PropEvent::Remove(id) => self.prop_manager.remove_by_id(id),
+                PropEvent::Remove(id) => {
+                    self.prop_manager.remove_by_id(id);
+                    self.grid.remove_prop(id);
+                }
             }
         }
     }
 
     fn process_snake_events(&mut self, evs: Vec<SnakeEvent>) {
+        let mut cut = Vec::new();
+
         for ev in evs {
-            let sn = self.snake_by_id(ev.id);
+            // the snake may have been removed earlier in the tick
+            let sn = match self.snakes.get_mut(&ev.id) {
+                Some(sn) => sn,
+                None => continue,
+            };
 
             match ev.cmd {
-                Cmd::Kill => sn.kill(),
+                Cmd::Kill => {
+                    if !sn.is_dead {
+                        self.events.push(GameEvent::Died(sn.id));
+                    }
+                    sn.kill(self.death_ticks);
+                }
                 Cmd::Grow(n) => sn.grow(n),
                 Cmd::GiveScore(n) => sn.give_score(n),
                 Cmd::GiveBuff(b) => sn.give_buff(b.get()),
+                Cmd::ChangeSpeed(speed, ticks) => sn.change_speed(speed, ticks),
+                Cmd::Shrink(n) => sn.shrink(n, &mut self.grid),
+                Cmd::Freeze(ticks) => sn.freeze(ticks),
+
+                // a dead or spawning snake is not where its head is
+                Cmd::Reverse if sn.is_mortal() => sn.reverse(&mut self.grid),
+                Cmd::TeleportTo(idx) if sn.is_mortal() => {
+                    sn.teleport(idx, &mut self.grid)
+                }
+                Cmd::Reverse | Cmd::TeleportTo(_) => (),
+
+                Cmd::Sever(idx) => cut.extend(sn.sever(idx, &mut self.grid)),
+            }
+        }
+
+        if self.tail_cutting == TailCutting::Food {
+            for idx in cut {
+                if self.grid.is_vacant(idx) {
+                    let pid = self.next_prop_id();
+                    self.add_prop(pid, idx, Box::new(Food::GrowFood));
+                }
             }
         }
     }
 
+    pub fn rows(&self) -> usize {
+        self.grid.rows()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.grid.cols()
+    }
+
+    // What the grid shows at idx, which has to be inside the grid.
+    pub fn tag_at(&self, idx: Index2D) -> Tag {
+        self.grid.tag(idx)
+    }
+
+    pub fn snakes(&self) -> impl Iterator<Item = SnakeView<'_>> + '_ {
+        self.snakes.values().map(move |sn| SnakeView {
+            snake: sn,
+            team: self.get_team(sn.id),
+        })
+    }
+
+    pub fn snake(&self, id: SnakeID) -> Option<SnakeView<'_>> {
+        self.snakes.get(&id).map(|sn| SnakeView {
+            snake: sn,
+            team: self.get_team(id),
+        })
+    }
+
+    pub fn props(&self) -> impl Iterator<Item = PropView<'_>> + '_ {
+        self.prop_manager
+            .props
+            .iter()
+            .filter_map(move |(timer, pid, idx)| {
+                self.grid.prop(*pid).map(|prop| PropView {
+                    id: *pid,
+                    pos: *idx,
+                    timer: timer,
+                    prop: prop,
+                })
+            })
+    }
+
     pub fn builder() -> GameStateBuilder {
         GameStateBuilder::new()
     }
 
+    // Adds a snake to the team with the fewest members, if there are teams.
     pub fn add_snake(&mut self) -> Option<SnakeID> {
+        let smallest = (0..self.team_count as TeamID).min_by_key(|team| {
+            self.teams.values().filter(|team2| team == *team2).count()
+        });
+
+        match smallest {
+            Some(team) => self.add_snake_to_team(team),
+
+            None => {
+                let id = self.next_snake_id();
+                let spawned =
+                    GameState::spawn_snake(&self.grid, id, self.spawn_ticks);
+                spawned.map(|sn| {
+                    self.snakes.insert(id, sn);
+                    id
+                })
+            }
+        }
+    }
+
+    pub fn add_snake_to_team(&mut self, team: TeamID) -> Option<SnakeID> {
+        if self.team_count as TeamID <= team {
+            return None;
+        }
+
         let id = self.next_snake_id();
-        GameState::spawn_snake(&self.grid, id).map(|sn| {
+        let spawned = GameState::spawn_snake(&self.grid, id, self.spawn_ticks);
+        spawned.map(|sn| {
             self.snakes.insert(id, sn);
+            self.teams.insert(id, team);
             id
         })
     }
 
+    // Takes the snake out of the game, either at once or, with
+    // GameStateBuilder::with_decay_on_remove, by letting it die and decay
+    // like any other snake, after which it is removed instead of respawned.
     pub fn remove_snake(&mut self, id: SnakeID) -> Result<(), ()> {
+        if self.decay_on_remove {
+            let sn = match self.snakes.get_mut(&id) {
+                Some(sn) if !sn.is_leaving => sn,
+                _ => return Err(()),
+            };
+
+            if !sn.is_dead {
+                // dead snakes have no head in the grid
+                self.grid.remove_owned(sn.pos, sn.id);
+                // leaving snakes do not wait to respawn
+                sn.kill(0);
+            }
+
+            sn.is_leaving = true;
+            return Ok(());
+        }
+
+        self.teams.remove(&id);
+
         match self.snakes.remove(&id) {
             None => Err(()),
-            Some(_) => Ok(()),
+            Some(mut sn) => {
+                sn.remove_all(&mut self.grid);
+                Ok(())
+            }
         }
     }
 
@@ -640,6 +2350,33 @@ impl GameState {
         self.snakes.get(&id).map(|sn| sn.score as u64)
     }
 
+    pub fn get_team(&self, id: SnakeID) -> Option<TeamID> {
+        self.teams.get(&id).cloned()
+    }
+
+    // The summed score of every member of the team.
+    pub fn get_team_score(&self, team: TeamID) -> u64 {
+        self.teams
+            .iter()
+            .filter(|(_, team2)| team == **team2)
+            .filter_map(|(id, _)| self.get_score(*id))
+            .sum()
+    }
+
+    // The team that won according to the game mode, if any. When several
+    // teams qualify, the one with the highest score wins.
+    pub fn winning_team(&self) -> Option<TeamID> {
+        match self.mode {
+            GameMode::Endless => None,
+
+            GameMode::ScoreLimit(limit) => (0..self.team_count as TeamID)
+                .map(|team| (team, self.get_team_score(team)))
+                .filter(|(_, score)| limit <= *score)
+                .max_by_key(|(_, score)| *score)
+                .map(|(team, _)| team),
+        }
+    }
+
     pub fn give_direction(
         &mut self,
         id: SnakeID,
@@ -654,6 +2391,32 @@ impl GameState {
         }
     }
 
+    // Trades the last body cell for a short burst of speed.
+    pub fn boost(&mut self, id: SnakeID) -> Result<(), ()> {
+        let sn = match self.snakes.get_mut(&id) {
+            Some(sn) => sn,
+            None => return Err(()),
+        };
+
+        if !sn.is_mortal() || !sn.shed_tail(&mut self.grid) {
+            return Err(());
+        }
+
+        let ticks = NonZeroUsize::new(BOOST_TICKS).unwrap();
+        sn.change_speed(Speed::Fast(NonZeroUsize::new(2).unwrap()), ticks);
+        Ok(())
+    }
+
+    // Applies cmd to the snake as if a prop had given it.
+    pub fn give_cmd(&mut self, id: SnakeID, cmd: Cmd) -> Result<(), ()> {
+        if !self.snakes.contains_key(&id) {
+            return Err(());
+        }
+
+        self.process_snake_events(vec![SnakeEvent { id: id, cmd: cmd }]);
+        Ok(())
+    }
+
     pub fn get_grid_data(&self) -> GridData {
         self.grid.data()
     }
@@ -663,11 +2426,13 @@ impl GameState {
             let mut came_from_heads: HashMap<SnakeID, CameFrom> = HashMap::new();
             let mut came_from_tails: HashMap<SnakeID, CameFrom> = HashMap::new();
 
-            for sn in self.snakes.values_mut() {
-                if let Some(x) = sn.came_from_head.take() {
+            // came_froms are kept around until they are done animating,
+            // see CameFrom::age
+            for sn in self.snakes.values() {
+                if let Some(x) = sn.came_from_head.clone() {
                     came_from_heads.insert(sn.id, x);
                 }
-                if let Some(x) = sn.came_from_tail.take() {
+                if let Some(x) = sn.came_from_tail.clone() {
                     came_from_tails.insert(sn.id, x);
                 }
             }
@@ -677,6 +2442,33 @@ impl GameState {
             Some(GameData {
                 came_from_heads: came_from_heads,
                 came_from_tails: came_from_tails,
+                came_from_props: self.came_from_props.clone(),
+                snakes: self
+                    .snakes
+                    .values()
+                    .filter(|sn| !sn.is_dead && sn.spawn_timer.is_done())
+                    .map(|sn| {
+                        let cells = std::iter::once(&sn.pos)
+                            .chain(sn.body.iter())
+                            .map(Index2D::get_u32)
+                            .collect();
+
+                        let data = SnakeCells {
+                            cells: cells,
+                            direction: sn.curr_dir,
+                            speed: sn.speed(),
+                            grow: sn.grow_count.map_or(0, |n| n.get() as u32),
+                        };
+
+                        (sn.id, data)
+                    })
+                    .collect(),
+                teams: self.teams.clone(),
+                scores: self
+                    .snakes
+                    .values()
+                    .map(|sn| (sn.id, sn.score as u64))
+                    .collect(),
                 grid_data: self.get_grid_data(),
             })
         } else {
@@ -34,6 +34,7 @@ impl NonZeroUsizeMath for NonZeroUsize {
 // + Timer +
 // +++++++++
 
+#[derive(Debug, Clone, Copy)]
 pub struct Timer(usize, usize);
 
 impl Timer {
@@ -49,6 +50,10 @@ impl Timer {
         self.1 <= self.0
     }
 
+    pub fn remaining(&self) -> usize {
+        self.1.saturating_sub(self.0)
+    }
+
     pub fn reset(&mut self) {
         mem::replace(self, Timer(0, self.1));
     }
@@ -58,7 +63,7 @@ impl Timer {
 // + Direction +
 // +++++++++++++
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
@@ -78,13 +83,54 @@ impl Direction {
         let mut rng = rand::thread_rng();
         *dirs.iter().choose(&mut rng).unwrap()
     }
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+// +++++++++
+// + Speed +
+// +++++++++
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Speed {
+    // moves one cell every nth tick
+    Slow(NonZeroUsize),
+    // moves n cells every tick
+    Fast(NonZeroUsize),
+}
+
+impl Speed {
+    pub fn normal() -> Self {
+        Speed::Fast(NonZeroUsize::one())
+    }
+
+    pub fn cells_per_tick(&self) -> usize {
+        match self {
+            Speed::Slow(_) => 1,
+            Speed::Fast(n) => n.get(),
+        }
+    }
+
+    pub fn ticks_per_move(&self) -> usize {
+        match self {
+            Speed::Slow(n) => n.get(),
+            Speed::Fast(_) => 1,
+        }
+    }
 }
 
 // +++++++++++
 // + Index2D +
 // +++++++++++
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Index2D(i32, i32);
 
 fn wrap(x: i32, lo: i32, hi: i32) -> i32 {
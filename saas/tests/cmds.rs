@@ -0,0 +1,151 @@
+extern crate saas;
+
+use std::num::NonZeroUsize;
+
+use saas::entity::*;
+use saas::state::*;
+use saas::util::*;
+
+// One snake on an otherwise empty grid, done spawning and n + 1 cells
+// long. Props are kept out of the way.
+fn lone_snake(n: usize) -> (GameState, SnakeID) {
+    let mut st = GameState::builder()
+        .with_dimensions(32, 32)
+        .with_prop_spawn_timer(Timer::new(10_000))
+        .build();
+
+    let id = st.add_snake().unwrap();
+
+    for _ in 0..6 {
+        st.tick();
+    }
+
+    let grow = Cmd::Grow(NonZeroUsize::new(n).unwrap());
+    st.give_cmd(id, grow).unwrap();
+
+    for _ in 0..n {
+        st.tick();
+    }
+
+    (st, id)
+}
+
+fn is_vacant(st: &GameState, idx: Index2D) -> bool {
+    match st.tag_at(idx).kind {
+        Kind::None => true,
+        _ => false,
+    }
+}
+
+#[test]
+fn shrink_cuts_the_tail() {
+    let (mut st, id) = lone_snake(6);
+    let body: Vec<Index2D> = st.snake(id).unwrap().body().collect();
+    let len = st.snake(id).unwrap().len();
+
+    let shrink = Cmd::Shrink(NonZeroUsize::new(2).unwrap());
+    st.give_cmd(id, shrink).unwrap();
+
+    assert_eq!(st.snake(id).unwrap().len(), len - 2);
+    for idx in body.iter().rev().take(2) {
+        assert!(is_vacant(&st, *idx));
+    }
+
+    let shrink = Cmd::Shrink(NonZeroUsize::new(100).unwrap());
+    st.give_cmd(id, shrink).unwrap();
+
+    assert_eq!(st.snake(id).unwrap().len(), 2);
+    assert!(st.snake(id).unwrap().is_alive());
+}
+
+#[test]
+fn reverse_swaps_head_and_tail() {
+    let (mut st, id) = lone_snake(4);
+    let head = st.snake(id).unwrap().head();
+    let body: Vec<Index2D> = st.snake(id).unwrap().body().collect();
+
+    st.give_cmd(id, Cmd::Reverse).unwrap();
+
+    let sn = st.snake(id).unwrap();
+    let mut expected: Vec<Index2D> =
+        body.iter().rev().skip(1).cloned().collect();
+    expected.push(head);
+
+    assert_eq!(sn.head(), *body.last().unwrap());
+    assert_eq!(sn.body().collect::<Vec<_>>(), expected);
+
+    if let Kind::SnakeHead = st.tag_at(sn.head()).kind {
+    } else {
+        panic!("the new head is not in the grid");
+    }
+
+    // the snake moves on in the direction its old tail was pointing
+    let new_head = sn.head();
+    st.tick();
+
+    let sn = st.snake(id).unwrap();
+    assert!(sn.is_alive());
+    assert_eq!(sn.body().next(), Some(new_head));
+    assert!(!expected.contains(&sn.head()));
+}
+
+#[test]
+fn teleport_moves_the_head() {
+    let (mut st, id) = lone_snake(3);
+    let head = st.snake(id).unwrap().head();
+    let (i, j) = head.get();
+    let to = Index2D::new((i + 16) % 32, (j + 16) % 32);
+    assert!(is_vacant(&st, to));
+
+    st.give_cmd(id, Cmd::TeleportTo(to)).unwrap();
+
+    assert_eq!(st.snake(id).unwrap().head(), to);
+    assert!(is_vacant(&st, head));
+    if let Kind::SnakeHead = st.tag_at(to).kind {
+    } else {
+        panic!("the head is not where it teleported to");
+    }
+
+    // the body follows through the gap
+    st.tick();
+    let sn = st.snake(id).unwrap();
+    assert!(sn.is_alive());
+    assert_eq!(sn.body().next(), Some(to));
+}
+
+#[test]
+fn teleport_needs_a_vacant_cell() {
+    let (mut st, id) = lone_snake(3);
+    let head = st.snake(id).unwrap().head();
+    let tail = st.snake(id).unwrap().body().last().unwrap();
+
+    st.give_cmd(id, Cmd::TeleportTo(tail)).unwrap();
+    st.give_cmd(id, Cmd::TeleportTo(Index2D::new(100, 100))).unwrap();
+
+    assert_eq!(st.snake(id).unwrap().head(), head);
+}
+
+#[test]
+fn freeze_stops_the_snake() {
+    let (mut st, id) = lone_snake(2);
+    let head = st.snake(id).unwrap().head();
+
+    let freeze = Cmd::Freeze(NonZeroUsize::new(3).unwrap());
+    st.give_cmd(id, freeze).unwrap();
+
+    for _ in 0..3 {
+        st.tick();
+        assert_eq!(st.snake(id).unwrap().head(), head);
+    }
+
+    st.tick();
+    assert_ne!(st.snake(id).unwrap().head(), head);
+}
+
+#[test]
+fn cmds_need_a_snake() {
+    let (mut st, id) = lone_snake(1);
+    st.remove_snake(id).unwrap();
+
+    assert!(st.give_cmd(id, Cmd::Reverse).is_err());
+}
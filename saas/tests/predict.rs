@@ -0,0 +1,125 @@
+extern crate saas;
+
+use saas::entity::*;
+use saas::scenario::*;
+use saas::state::*;
+use saas::util::*;
+
+// A snake that is done spawning on a grid without props, along with the
+// window a client centered on it would get.
+fn game() -> (GameState, SnakeID, GameData) {
+    let mut st = GameState::builder()
+        .with_dimensions(40, 40)
+        .with_prop_spawn_timer(Timer::new(10_000))
+        .build();
+
+    let id = st.add_snake().unwrap();
+
+    for _ in 0..8 {
+        st.tick();
+    }
+
+    let gd = window(&mut st, id);
+    (st, id, gd)
+}
+
+fn window(st: &mut GameState, id: SnakeID) -> GameData {
+    window_of(st, id, 12)
+}
+
+fn window_of(st: &mut GameState, id: SnakeID, radius: usize) -> GameData {
+    let head = st.snake(id).unwrap().head();
+    st.get_game_data().unwrap().window(head, radius)
+}
+
+// The snake's cells from the head to the tail.
+fn cells(st: &GameState, id: SnakeID) -> Vec<Index2D> {
+    let sn = st.snake(id).unwrap();
+    Some(sn.head()).into_iter().chain(sn.body()).collect()
+}
+
+// Where idx of a 40 by 40 grid is in gd.
+fn in_window(gd: &GameData, idx: Index2D) -> (usize, usize) {
+    let (i, j) = idx.get();
+    let top = gd.grid_data.top as usize;
+    let left = gd.grid_data.left as usize;
+    ((i + 40 - top) % 40, (j + 40 - left) % 40)
+}
+
+#[test]
+fn windows_keep_whole_snakes() {
+    let (st, id, gd) = game();
+    let sn = st.snake(id).unwrap();
+    let data = gd.snakes.get(&id).unwrap();
+
+    assert_eq!(data.cells.len(), sn.len());
+    assert_eq!(data.cells[0], (12, 12));
+    assert_eq!(data.direction, sn.direction());
+}
+
+#[test]
+fn prediction_follows_the_server() {
+    let (mut st, id, gd) = game();
+    let mut predicted = GameState::predicting(&gd, id, 40, 40).unwrap();
+
+    let dir = match st.snake(id).unwrap().direction() {
+        Direction::Up | Direction::Down => Direction::Left,
+        Direction::Left | Direction::Right => Direction::Up,
+    };
+
+    st.give_direction(id, dir).unwrap();
+    predicted.give_direction(id, dir).unwrap();
+
+    for _ in 0..4 {
+        st.tick();
+        predicted.tick();
+    }
+
+    // the server's data is windowed around where the snake was before
+    let expected: Vec<_> =
+        cells(&st, id).into_iter().map(|x| in_window(&gd, x)).collect();
+    let cells: Vec<_> =
+        cells(&predicted, id).into_iter().map(|x| x.get()).collect();
+
+    assert_eq!(cells, expected);
+}
+
+#[test]
+fn prediction_wraps_where_the_map_does() {
+    let (mut st, id, _) = game();
+
+    // game data is only made once a tick
+    st.tick();
+    let gd = window_of(&mut st, id, 2);
+    let mut predicted = GameState::predicting(&gd, id, 40, 40).unwrap();
+
+    // out of the window, which is not where the map ends
+    for _ in 0..4 {
+        st.tick();
+        predicted.tick();
+    }
+
+    let head = predicted.snake(id).unwrap().head();
+    assert_eq!(head.get(), in_window(&gd, st.snake(id).unwrap().head()));
+}
+
+#[test]
+fn prediction_keeps_growing() {
+    let mut sc = Scenario::new(
+        "
+        .>**....
+        ",
+    );
+
+    // the second piece of food is eaten before the first has grown
+    sc.wait(2);
+    let gd = sc.state_mut().get_game_data().unwrap();
+    let mut predicted = GameState::predicting(&gd, 0, 1, 8).unwrap();
+
+    sc.wait(4);
+    for _ in 0..4 {
+        predicted.tick();
+    }
+
+    assert_eq!(cells(&predicted, 0), cells(sc.state(), 0));
+}
@@ -0,0 +1,101 @@
+extern crate saas;
+
+use saas::entity::*;
+use saas::rollback::*;
+use saas::scenario::*;
+use saas::state::*;
+use saas::util::*;
+
+// Two snakes that are done spawning, with nothing random left to happen.
+const PICTURE: &str = "
+    ............
+    ..>.........
+    ............
+    ............
+    .........<..
+    ............
+    ";
+
+// The snake's cells from the head to the tail.
+fn cells(st: &GameState, id: SnakeID) -> Vec<Index2D> {
+    let sn = st.snake(id).unwrap();
+    Some(sn.head()).into_iter().chain(sn.body()).collect()
+}
+
+fn assert_same(st: &GameState, expected: &GameState) {
+    assert_eq!(st.ticks(), expected.ticks());
+
+    for id in 0..2 {
+        assert_eq!(cells(st, id), cells(expected, id));
+        let dir = st.snake(id).unwrap().direction();
+        assert_eq!(dir, expected.snake(id).unwrap().direction());
+    }
+}
+
+fn turn(id: SnakeID, dir: Direction) -> Vec<(SnakeID, Input)> {
+    vec![(id, Input::Direction(dir))]
+}
+
+#[test]
+fn resimulating_plays_like_the_first_time() {
+    let mut rb = Rollback::new(Scenario::new(PICTURE).state().clone(), 8);
+    for _ in 0..5 {
+        rb.tick();
+    }
+
+    // the snakes turned at ticks 2 and 3 after all
+    let inputs = vec![
+        turn(0, Direction::Down),
+        turn(1, Direction::Up),
+        Vec::new(),
+    ];
+    rb.rewind(2).unwrap();
+    rb.resimulate(&inputs);
+
+    let mut sc = Scenario::new(PICTURE);
+    sc.wait(2);
+    sc.run(&inputs);
+
+    assert_same(rb.state(), sc.state());
+    assert_eq!(rb.state().snake(0).unwrap().direction(), Direction::Down);
+}
+
+#[test]
+fn rewinding_to_now_does_nothing() {
+    let mut rb = Rollback::new(Scenario::new(PICTURE).state().clone(), 8);
+    for _ in 0..3 {
+        rb.tick();
+    }
+
+    let mut sc = Scenario::new(PICTURE);
+    sc.wait(3);
+
+    rb.rewind(3).unwrap();
+    assert_same(rb.state(), sc.state());
+}
+
+#[test]
+fn rewinding_past_the_history_fails() {
+    let mut rb = Rollback::new(Scenario::new(PICTURE).state().clone(), 3);
+    for _ in 0..5 {
+        rb.tick();
+    }
+
+    // only the starts of the last three ticks are kept
+    assert_eq!(rb.oldest(), 2);
+    assert!(rb.rewind(1).is_err());
+    assert!(rb.rewind(6).is_err());
+
+    // and failing leaves the game as it was
+    let mut sc = Scenario::new(PICTURE);
+    sc.wait(5);
+    assert_same(rb.state(), sc.state());
+
+    rb.rewind(2).unwrap();
+    sc = Scenario::new(PICTURE);
+    sc.wait(2);
+    assert_same(rb.state(), sc.state());
+
+    // the states after the one rewound to are gone
+    assert!(rb.rewind(3).is_err());
+}
@@ -0,0 +1,667 @@
+extern crate saas;
+
+use saas::entity::*;
+use saas::rollback::*;
+use saas::scenario::*;
+use saas::state::*;
+use saas::util::*;
+
+#[test]
+fn head_on() {
+    let mut sc = Scenario::new(
+        "
+        .>.<.
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Died(0), GameEvent::Died(1)]);
+}
+
+#[test]
+fn swap_with_bodies() {
+    let mut sc = Scenario::new(
+        "
+        o><o
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Died(0), GameEvent::Died(1)]);
+    sc.assert_picture(
+        "
+        .oo.
+        ",
+    );
+}
+
+#[test]
+fn eat_and_grow() {
+    let mut sc = Scenario::new(
+        "
+        >*...
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Ate(0, 0)]);
+    sc.assert_picture(
+        "
+        .>...
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[]);
+    sc.assert_picture(
+        "
+        .o>..
+        ",
+    );
+}
+
+#[test]
+fn eat_fast_food() {
+    let mut sc = Scenario::new(
+        "
+        >+......
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Ate(0, 2)]);
+    sc.assert_picture(
+        "
+        .>......
+        ",
+    );
+
+    // two cells a tick from then on
+    sc.wait(1);
+    sc.assert_picture(
+        "
+        ...>....
+        ",
+    );
+}
+
+#[test]
+fn eat_slow_food() {
+    let mut sc = Scenario::new(
+        "
+        >-......
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Ate(0, 3)]);
+
+    // one cell every other tick from then on
+    sc.wait(1);
+    sc.assert_picture(
+        "
+        .>......
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_picture(
+        "
+        ..>.....
+        ",
+    );
+}
+
+#[test]
+fn boost() {
+    let mut sc = Scenario::new(
+        "
+        oooo>..........
+        ",
+    );
+
+    // boosting costs the tail
+    sc.run(&[vec![(0, Input::Boost)]]);
+    sc.assert_picture(
+        "
+        ...ooo>........
+        ",
+    );
+
+    sc.wait(3);
+    sc.assert_picture(
+        "
+        .........ooo>..
+        ",
+    );
+
+    // and lasts four ticks
+    sc.wait(1);
+    sc.assert_picture(
+        "
+        ..........ooo>.
+        ",
+    );
+}
+
+#[test]
+fn catch_a_mouse() {
+    let mut sc = Scenario::new(
+        "
+        >..m......
+        ",
+    );
+
+    // it catches its breath every other tick
+    sc.wait(1);
+    sc.assert_picture(
+        "
+        .>.m......
+        ",
+    );
+
+    // and runs from the head otherwise
+    sc.wait(1);
+    sc.assert_picture(
+        "
+        ..>.m.....
+        ",
+    );
+
+    sc.wait(2);
+    sc.assert_events(&[GameEvent::Ate(0, 4)]);
+    sc.assert_picture(
+        "
+        ....>.....
+        ",
+    );
+}
+
+#[test]
+fn bonus_wanders() {
+    let mut sc = Scenario::new(
+        "
+        .....
+        .....
+        ..b..
+        .....
+        .....
+        ",
+    );
+
+    // every third tick it moves to a random neighbor
+    sc.wait(2);
+    assert_eq!(sc.state().props().next().unwrap().pos(), Index2D::new(2, 2));
+
+    sc.wait(1);
+    let pos = sc.state().props().next().unwrap().pos();
+    let neighbors = [(1, 2), (3, 2), (2, 1), (2, 3)];
+    assert!(neighbors.contains(&pos.get()), "the bonus is at {:?}", pos);
+}
+
+#[test]
+fn props_keep_off_hazards() {
+    let mut sc = Scenario::new(
+        "
+        #####
+        ##b##
+        #####
+        ",
+    );
+
+    sc.wait(9);
+    sc.assert_picture(
+        "
+        #####
+        ##b##
+        #####
+        ",
+    );
+}
+
+#[test]
+fn eat_a_golden_apple() {
+    let mut sc = Scenario::new(
+        "
+        .....
+        >$$..
+        .$$..
+        ",
+    );
+
+    // the apple covers all of its cells
+    sc.assert_picture(
+        "
+        .....
+        >$$..
+        .$$..
+        ",
+    );
+
+    // and running into one of them eats it whole
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Ate(0, 6)]);
+    sc.assert_picture(
+        "
+        .....
+        .>...
+        .....
+        ",
+    );
+}
+
+#[test]
+fn turn() {
+    let mut sc = Scenario::new(
+        "
+        .....
+        .oo>.
+        .....
+        ",
+    );
+
+    sc.run(&[vec![(0, Input::Direction(Direction::Up))]]);
+    sc.assert_picture(
+        "
+        ...^.
+        ..oo.
+        .....
+        ",
+    );
+}
+
+#[test]
+fn respawn() {
+    let mut sc = Scenario::new(
+        "
+        #>#
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Died(0)]);
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Respawned(0)]);
+    sc.assert_picture(
+        "
+        #@#
+        ",
+    );
+}
+
+#[test]
+fn head_on_over_a_hazard() {
+    let mut sc = Scenario::new(
+        "
+        o>=<o
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Died(0), GameEvent::Died(1)]);
+    sc.assert_picture(
+        "
+        .o=o.
+        ",
+    );
+}
+
+#[test]
+fn body_over_a_hazard() {
+    let mut sc = Scenario::new(
+        "
+        ....o...
+        ....v...
+        ........
+        .oo>=...
+        ........
+        ",
+    );
+
+    // snakes lie on top of hazards
+    sc.wait(1);
+    sc.assert_picture(
+        "
+        ........
+        ....o...
+        ....v...
+        ..oo>...
+        ........
+        ",
+    );
+
+    // so the body there is as deadly as anywhere else
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Died(0)]);
+    sc.assert_picture(
+        "
+        ........
+        ........
+        ....o...
+        ...oo>..
+        ........
+        ",
+    );
+
+    // and the hazard is back once the snake has moved on, the other one
+    // respawns wherever
+    sc.wait(2);
+    match sc.state().tag_at(Index2D::new(3, 4)).kind {
+        Kind::Hazard(Phase::Safe) => (),
+        kind => panic!("the hazard's cell holds {:?}", kind),
+    }
+}
+
+#[test]
+fn cross_a_teammate() {
+    let rules = GameState::builder().with_teams(1).with_friendly_fire(false);
+    let mut sc = Scenario::with_builder(
+        "
+        .....o......
+        .....v......
+        ............
+        ..<ooooooo..
+        ............
+        ",
+        rules,
+    );
+
+    // the head lies on top of the body it crosses
+    sc.wait(2);
+    sc.assert_picture(
+        "
+        ............
+        ............
+        .....o......
+        <oooovoo....
+        ............
+        ",
+    );
+
+    // and the body is back once the other snake has moved on
+    sc.wait(2);
+    sc.assert_events(&[]);
+    sc.assert_picture(
+        "
+        .....v......
+        ............
+        ............
+        oooooo....<o
+        .....o......
+        ",
+    );
+
+    let tag = sc.state().tag_at(Index2D::new(3, 5));
+    match tag.kind {
+        Kind::SnakeBody => assert_eq!(tag.id, 1),
+        kind => panic!("the crossed cell holds {:?}", kind),
+    }
+}
+
+// A head running into the middle of another snake at the third tick.
+const CUT: &str = "
+    ....v.....
+    ..........
+    ..........
+    ....<ooooo
+    ";
+
+#[test]
+fn cut_a_tail() {
+    let rules = GameState::builder().with_tail_cutting(TailCutting::Vanish);
+    let mut sc = Scenario::with_builder(CUT, rules);
+
+    // the head survives and what is behind it is gone
+    sc.wait(3);
+    sc.assert_events(&[]);
+    sc.assert_picture(
+        "
+        ..........
+        ..........
+        ..........
+        .<oov.....
+        ",
+    );
+}
+
+#[test]
+fn cut_a_tail_into_food() {
+    let rules = GameState::builder().with_tail_cutting(TailCutting::Food);
+    let mut sc = Scenario::with_builder(CUT, rules);
+
+    sc.wait(3);
+    sc.assert_events(&[]);
+    sc.assert_picture(
+        "
+        ..........
+        ..........
+        ..........
+        .<oov**...
+        ",
+    );
+}
+
+#[test]
+fn remove_a_snake() {
+    let mut sc = Scenario::new(
+        "
+        .......
+        .ooo>..
+        .......
+        ",
+    );
+
+    // it is gone at once
+    sc.state_mut().remove_snake(0).unwrap();
+    assert!(sc.state().snake(0).is_none());
+    sc.assert_picture(
+        "
+        .......
+        .......
+        .......
+        ",
+    );
+
+    sc.wait(3);
+    sc.assert_events(&[]);
+    assert!(sc.state().snake(0).is_none());
+}
+
+#[test]
+fn remove_a_snake_that_decays() {
+    let rules = GameState::builder()
+        .with_decay_on_remove(true)
+        .with_death_ticks(2);
+    let mut sc = Scenario::with_builder(
+        "
+        .......
+        .ooo>..
+        .......
+        ",
+        rules,
+    );
+
+    // the head goes and the body decays from the tail like a dead snake's
+    sc.state_mut().remove_snake(0).unwrap();
+    sc.assert_picture(
+        "
+        .......
+        .ooo...
+        .......
+        ",
+    );
+
+    sc.wait(2);
+    sc.assert_picture(
+        "
+        .......
+        .o.....
+        .......
+        ",
+    );
+
+    // it does not wait out death_ticks once the body is gone
+    sc.wait(2);
+    sc.assert_events(&[GameEvent::Left(0)]);
+    assert!(sc.state().snake(0).is_none());
+    sc.assert_picture(
+        "
+        .......
+        .......
+        .......
+        ",
+    );
+
+    // and never respawns
+    sc.wait(5);
+    sc.assert_events(&[]);
+    assert!(sc.state().snake(0).is_none());
+}
+
+#[test]
+fn pause_snakes() {
+    let mut sc = Scenario::new(
+        "
+        ........
+        .oo>....
+        ........
+        ",
+    );
+
+    sc.state_mut().pause();
+    sc.wait(5);
+    assert_eq!(sc.state().ticks(), 0);
+    sc.assert_picture(
+        "
+        ........
+        .oo>....
+        ........
+        ",
+    );
+
+    sc.state_mut().resume();
+    sc.wait(1);
+    sc.assert_picture(
+        "
+        ........
+        ..oo>...
+        ........
+        ",
+    );
+}
+
+#[test]
+fn pause_respawning() {
+    let mut sc = Scenario::new(
+        "
+        #>#
+        ",
+    );
+
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Died(0)]);
+
+    // the snake would have respawned at the next tick
+    sc.state_mut().pause();
+    sc.wait(5);
+    sc.assert_events(&[]);
+    assert!(!sc.state().snake(0).unwrap().is_alive());
+
+    sc.state_mut().resume();
+    sc.wait(1);
+    sc.assert_events(&[GameEvent::Respawned(0)]);
+}
+
+#[test]
+fn pause_prop_spawning() {
+    let mut st = GameState::builder()
+        .with_dimensions(5, 5)
+        .with_prop_spawn_timer(Timer::new(3))
+        .build();
+
+    // a prop spawns at the fourth tick
+    st.tick();
+    st.tick();
+    st.pause();
+    for _ in 0..5 {
+        st.tick();
+    }
+    assert_eq!(st.props().count(), 0);
+
+    st.resume();
+    st.tick();
+    assert_eq!(st.props().count(), 0);
+    st.tick();
+    assert_eq!(st.props().count(), 1);
+}
+
+#[test]
+fn step_a_paused_game() {
+    let mut sc = Scenario::new(
+        "
+        ........
+        .oo>....
+        ........
+        ",
+    );
+
+    sc.state_mut().pause();
+    sc.state_mut().step();
+    assert_eq!(sc.state().ticks(), 1);
+    assert!(sc.state().is_paused());
+    sc.assert_picture(
+        "
+        ........
+        ..oo>...
+        ........
+        ",
+    );
+
+    // and stays paused
+    sc.wait(3);
+    assert_eq!(sc.state().ticks(), 1);
+    sc.assert_picture(
+        "
+        ........
+        ..oo>...
+        ........
+        ",
+    );
+}
+
+#[test]
+fn look_at_snakes_and_props() {
+    let mut sc = Scenario::new(
+        "
+        ......-
+        .oo>*..
+        .......
+        ",
+    );
+
+    sc.run(&[vec![], vec![(0, Input::Direction(Direction::Up))]]);
+    sc.assert_picture(
+        "
+        ....^.-
+        ..ooo..
+        .......
+        ",
+    );
+
+    let sn = sc.state().snake(0).unwrap();
+    assert_eq!(sn.head(), Index2D::new(0, 4));
+    let body: Vec<(usize, usize)> = sn.body().map(|idx| idx.get()).collect();
+    assert_eq!(body, vec![(1, 4), (1, 3), (1, 2)]);
+    assert_eq!(sn.len(), 4);
+    assert_eq!(sn.direction(), Direction::Up);
+    assert_eq!(sn.score(), 1);
+    assert!(sn.is_alive());
+
+    // the food was eaten, the slow food has two of its 20 ticks behind it
+    let props: Vec<PropView> = sc.state().props().collect();
+    assert_eq!(props.len(), 1);
+    assert_eq!(props[0].pos(), Index2D::new(0, 6));
+    assert_eq!(props[0].kind(), 3);
+    assert_eq!(props[0].ticks_left(), Some(18));
+}
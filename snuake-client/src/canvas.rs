@@ -4,7 +4,7 @@ use stdweb::traits::*;
 use stdweb::unstable::TryInto;
 use stdweb::web::html_element::ImageElement;
 use stdweb::web::html_element::CanvasElement;
-use stdweb::web::{document, CanvasRenderingContext2d};
+use stdweb::web::{document, CanvasRenderingContext2d, TextAlign};
 
 use crate::graphics::*;
 
@@ -46,6 +46,20 @@ impl Canvas {
             .unwrap();
     }
 
+    pub fn draw_text(
+        &self,
+        text: &str,
+        color: &str,
+        x: i32,
+        y: i32,
+        align: TextAlign,
+    ) {
+        self.ctx.set_fill_style_color(color);
+        self.ctx.set_font(FONT);
+        self.ctx.set_text_align(align);
+        self.ctx.fill_text(text, x.into(), y.into(), None);
+    }
+
     pub fn translate(&self, x: i32, y: i32) {
         self.ctx.translate(x.into(), y.into());
     }
@@ -69,6 +83,9 @@ pub struct GridCanvas<'a> {
     canvas: &'a Canvas,
     width: i32,
     height: i32,
+    // added to every position, see GridCanvas::follow
+    camera_x: i32,
+    camera_y: i32,
 }
 
 impl<'a> GridCanvas<'a> {
@@ -80,12 +97,21 @@ impl<'a> GridCanvas<'a> {
             canvas,
             width,
             height,
+            camera_x: 0,
+            camera_y: 0,
         }
     }
 
+    // Shifts everything drawn by the given number of cells, so that the
+    // camera keeps up with something that is still moving.
+    pub fn follow(&mut self, di: f64, dj: f64) {
+        self.camera_x = (dj * self.width as f64) as i32;
+        self.camera_y = (di * self.height as f64) as i32;
+    }
+
     pub fn draw_rect_at(&self, color: &str, x: i32, y: i32) {
-        let x = x * self.width;
-        let y = y * self.height;
+        let x = x * self.width + self.camera_x;
+        let y = y * self.height + self.camera_y;
 
         self.canvas.draw_rect(
             color,
@@ -105,8 +131,8 @@ impl<'a> GridCanvas<'a> {
         y_factor: f64,
         )
     {
-        let x = x * self.width;
-        let y = y * self.height;
+        let x = x * self.width + self.camera_x;
+        let y = y * self.height + self.camera_y;
 
         let translate_x = x_factor * self.width as f64;
         let translate_y = y_factor * self.height as f64;
@@ -132,8 +158,8 @@ impl<'a> GridCanvas<'a> {
         y_factor: f64,
         )
     {
-        let x = x * self.width;
-        let y = y * self.height;
+        let x = x * self.width + self.camera_x;
+        let y = y * self.height + self.camera_y;
 
         let width = (self.width as f64 * image.scale) as i32;
         let height = (self.height as f64 * image.scale) as i32;
@@ -158,6 +184,28 @@ impl<'a> GridCanvas<'a> {
         self.canvas.reset_transform();
     }
 
+    // Centered just above the cell, for names.
+    pub fn draw_text_at_translated(
+        &self,
+        text: &str,
+        x: i32,
+        y: i32,
+        x_factor: f64,
+        y_factor: f64,
+        )
+    {
+        let x = x * self.width + self.camera_x + self.width / 2;
+        let y = y * self.height + self.camera_y - self.height / 4;
+
+        let translate_x = x_factor * self.width as f64;
+        let translate_y = y_factor * self.height as f64;
+        self.canvas.translate(translate_x as i32, translate_y as i32);
+
+        self.canvas.draw_text(text, TEXT_COLOR, x, y, TextAlign::Center);
+
+        self.canvas.reset_transform();
+    }
+
     pub fn clear(&self, color: &str) {
         self.canvas.clear(color);
     }
@@ -1,8 +1,8 @@
 use stdweb::web::html_element::ImageElement;
 
-use std::str::FromStr;
 use std::collections::HashMap;
 
+use saas::entity::{Phase, ID};
 use snuake_shared::*;
 use crate::resource_loader::*;
 
@@ -14,6 +14,18 @@ pub struct Image {
 
 pub const BKG_COLOR: &str = "#2f8136";
 
+// for names and the scoreboard
+pub const TEXT_COLOR: &str = "#ffffff";
+pub const FONT: &str = "bold 14px sans-serif";
+
+pub fn get_hazard_color(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Safe => "#4c5359",
+        Phase::Warning => "#f29a2e",
+        Phase::Lethal => "#d63c3c",
+    }
+}
+
 const SNAKE_HEADS: &[&str] = &[
     "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/1f438.png",
     "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/1f435.png",
@@ -44,25 +56,16 @@ const HEAD_SCALING: &[f64] = &[
     1.65,
 ];
 
-const SNAKE_COLORS: &[&str] = &[
-    "#83bf4f",
-    "#89664c",
-    "#555e63",
-    "#f29a2e",
-    "#f5d1ac",
+const TEAM_COLORS: &[&str] = &[
+    "#d63c3c",
+    "#3c6fd6",
     "#e5bc5e",
-    "#947151",
-    "#4c5359",
-    "#f29a2e",
-    "#fc97b2",
-    "#89664c",
-    "#86959c",
+    "#9b59b6",
 ];
 
 pub struct SnakeGraphics {
     heads: ImageLoader<SnakeID>,
     scaling: HashMap<SnakeID, f64>,
-    colors: HashMap<SnakeID, String>,
 }
 
 impl SnakeGraphics {
@@ -78,15 +81,9 @@ impl SnakeGraphics {
             .map(|(i, scl)| (i as SnakeID, *scl))
             .collect();
 
-        let colors = SNAKE_COLORS.iter()
-            .enumerate()
-            .map(|(i, color)| (i as SnakeID, String::from_str(*color).unwrap()))
-            .collect();
-
         SnakeGraphics {
             heads,
             scaling,
-            colors,
         }
     }
 
@@ -100,19 +97,40 @@ impl SnakeGraphics {
         }
     }
 
+    // the same colour the server gives the player, see Player::color
     pub fn get_color(&self, id: SnakeID) -> &str {
-        self.colors.get(&id).unwrap()
+        PLAYER_COLORS[id as usize % PLAYER_COLORS.len()]
+    }
+
+    pub fn get_team_color(&self, team: TeamID) -> &str {
+        TEAM_COLORS[team as usize % TEAM_COLORS.len()]
     }
 }
 
 const FRUIT: &[&str] = &[
     "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/1f34e.png",
     "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/1f4a9.png",
+    "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/26a1.png",
+    "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/1f40c.png",
+    "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/1f401.png",
+    "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/2b50.png",
+    "http://raw.githubusercontent.com/EmojiTwo/emojitwo/master/png/1f34b.png",
 ];
 
 const FRUIT_SCALING: &[f64] = &[
     1.4,
     1.4,
+    1.4,
+    1.4,
+    1.4,
+    1.4,
+    2.8,
+];
+
+// Rows and cols of the kinds of props that cover more than one cell, see
+// Prop::id.
+const FRUIT_SIZE: &[(ID, (u32, u32))] = &[
+    (6, (2, 2)),
 ];
 
 pub struct PropGraphics {
@@ -148,6 +166,13 @@ impl PropGraphics {
             scale,
         }
     }
+
+    pub fn get_size(&self, kind: ID) -> (u32, u32) {
+        FRUIT_SIZE.iter()
+            .find(|(kind2, _)| *kind2 == kind)
+            .map(|(_, size)| *size)
+            .unwrap_or((1, 1))
+    }
 }
 
 pub struct Graphics {
@@ -2,10 +2,11 @@
 extern crate stdweb;
 
 use stdweb::traits::*;
-use stdweb::web::{event::KeyDownEvent, IEventTarget};
+use stdweb::web::{event::KeyDownEvent, IEventTarget, TextAlign};
 
 use std::rc::Rc;
 use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
 
 mod canvas;
 use crate::canvas::*;
@@ -38,9 +39,15 @@ impl TickTimer {
         }
     }
 
-    pub fn percent_left(&self, curr_ms: u64) -> f64 {
-        let elapsed = (curr_ms - self.tick_start) as f64;
-        1.0 - elapsed / self.tick_len as f64
+    // How much of a move is left to animate. The move is spread over
+    // `span` ticks, `step` of which passed before the current one.
+    pub fn percent_left(&self, curr_ms: u64, span: u32, step: u32) -> f64 {
+        let tick_len = self.tick_len as f64;
+        let elapsed = (curr_ms - self.tick_start) as f64
+            + step as f64 * tick_len;
+        let left = 1.0 - elapsed / (span as f64 * tick_len);
+
+        left.max(0.0).min(1.0)
     }
 }
 
@@ -120,12 +127,81 @@ fn draw_animated<'a, T : Clone>(
     }
 }
 
+// Draws the part of the trail the drawable is currently moving along.
+fn draw_trail<'a, T : Clone>(
+    draw: fn(&GridCanvas<'a>, T, i32, i32, f64, f64),
+    gc: &GridCanvas<'a>,
+    drawable: T,
+    trail: &Trail,
+    tick_timer: &TickTimer,
+    curr_ms: u64,
+) {
+    let percent_left = tick_timer.percent_left(curr_ms, trail.span, trail.step);
+
+    let n = trail.cells.len() - 1;
+    let moved = (1.0 - percent_left) * n as f64;
+    let k = (moved.floor() as usize).min(n - 1);
+
+    draw_animated(
+        draw,
+        gc,
+        drawable,
+        &trail.cells[k + 1],
+        &trail.cells[k],
+        1.0 - (moved - k as f64),
+    );
+}
+
+// How far behind the end of its trail something moving along it is drawn,
+// in cells. Trails crossing the edge of the grid wrap around.
+fn trail_lag(
+    trail: &Trail,
+    tick_timer: &TickTimer,
+    curr_ms: u64,
+) -> (f64, f64) {
+    let percent_left =
+        tick_timer.percent_left(curr_ms, trail.span, trail.step);
+
+    let n = trail.cells.len() - 1;
+    let moved = (1.0 - percent_left) * n as f64;
+
+    let step = |a: u32, b: u32| {
+        let d = b as i32 - a as i32;
+        if d.abs() <= 1 { d } else { -sgn(d) }
+    };
+
+    let mut lag = (0.0, 0.0);
+    for (k, cells) in trail.cells.windows(2).enumerate() {
+        let (i, j) = cells[0];
+        let (i2, j2) = cells[1];
+        let left = (k as f64 + 1.0 - moved).max(0.0).min(1.0);
+
+        lag.0 += step(i, i2) as f64 * left;
+        lag.1 += step(j, j2) as f64 * left;
+    }
+
+    lag
+}
+
+// Snakes playing in teams are coloured by team.
+fn snake_color<'a>(
+    gd: &GameData,
+    graphics: &'a Graphics,
+    id: SnakeID,
+) -> &'a str {
+    match gd.teams.get(&id) {
+        Some(team) => graphics.snake_graphics.get_team_color(*team),
+        None => graphics.snake_graphics.get_color(id),
+    }
+}
+
 trait Draw {
     fn draw(
         &self,
         gc: &GridCanvas,
         gr: &Graphics,
-        translate_factor: f64,
+        tick_timer: &TickTimer,
+        curr_ms: u64,
     );
 }
 
@@ -134,24 +210,25 @@ impl Draw for GameData {
         &self,
         grid_canvas: &GridCanvas,
         graphics: &Graphics,
-        translate_factor: f64,
+        tick_timer: &TickTimer,
+        curr_ms: u64,
         )
     {
         let rows = self.grid_data.rows as usize;
         let cols = self.grid_data.cols as usize;
 
         for (id, came_from) in self.came_from_tails.iter() {
-            if let CameFrom::Dummy((curr_pos, prev_pos)) = came_from {
-                draw_animated(
+            if let CameFrom::Dummy(trail) = came_from {
+                draw_trail(
                     GridCanvas::draw_rect_at_translated,
                     grid_canvas,
-                    graphics.snake_graphics.get_color(*id),
-                    curr_pos,
-                    prev_pos,
-                    translate_factor,
+                    snake_color(self, graphics, *id),
+                    trail,
+                    tick_timer,
+                    curr_ms,
                 );
 
-                let (i, j) = curr_pos;
+                let (i, j) = trail.cells.last().unwrap();
 
                 grid_canvas.draw_rect_at(
                     BKG_COLOR,
@@ -161,25 +238,46 @@ impl Draw for GameData {
             }
         }
 
+        // moving props are drawn along their trails below
+        let moving_props: HashSet<(u32, u32)> = self.came_from_props
+            .values()
+            .map(|came_from| *came_from.trail().cells.last().unwrap())
+            .collect();
+
         for i in 0 .. rows {
             for j in 0 .. cols {
-                let tag = self.grid_data.tags[i][j];
+                let tag = self.grid_data.get(i, j);
+                let is_moving = moving_props.contains(&(i as u32, j as u32));
                 let i = i as i32;
                 let j = j as i32;
 
                 match tag {
-                    Tag { kind: Kind::Prop, id } =>
+                    // the first part of a prop covering several cells is
+                    // drawn across all of them
+                    Tag { kind: Kind::Prop(part), id }
+                        if !is_moving && part == Part::origin() =>
+                    {
+                        let (h, w) = graphics.prop_graphics.get_size(id);
+
                         grid_canvas.draw_img_at_translated(
                             graphics.prop_graphics.get_img(id),
                             j,
                             i,
-                            0.0,
-                            0.0,
-                        ),
+                            (w - 1) as f64 / 2.0,
+                            (h - 1) as f64 / 2.0,
+                        )
+                    }
 
                     Tag { kind: Kind::SnakeBody, id } =>
                         grid_canvas.draw_rect_at(
-                            graphics.snake_graphics.get_color(id),
+                            snake_color(self, graphics, id),
+                            j,
+                            i,
+                        ),
+
+                    Tag { kind: Kind::Hazard(phase), .. } =>
+                        grid_canvas.draw_rect_at(
+                            get_hazard_color(phase),
                             j,
                             i,
                         ),
@@ -189,28 +287,43 @@ impl Draw for GameData {
             }
         }
 
+        for came_from in self.came_from_props.values() {
+            let trail = came_from.trail();
+            let (i, j) = *trail.cells.last().unwrap();
+            let tag = self.grid_data.get(i as usize, j as usize);
+
+            draw_trail(
+                GridCanvas::draw_img_at_translated,
+                grid_canvas,
+                graphics.prop_graphics.get_img(tag.id),
+                trail,
+                tick_timer,
+                curr_ms,
+            );
+        }
+
         for (id, came_from) in self.came_from_tails.iter() {
-            if let CameFrom::Real((curr_pos, prev_pos)) = came_from {
-                draw_animated(
+            if let CameFrom::Real(trail) = came_from {
+                draw_trail(
                     GridCanvas::draw_rect_at_translated,
                     grid_canvas,
-                    graphics.snake_graphics.get_color(*id),
-                    curr_pos,
-                    prev_pos,
-                    translate_factor,
+                    snake_color(self, graphics, *id),
+                    trail,
+                    tick_timer,
+                    curr_ms,
                 );
             }
         }
 
         for (id, came_from) in self.came_from_heads.iter() {
-            if let CameFrom::Real((curr_pos, prev_pos)) = came_from {
-                draw_animated(
+            if let CameFrom::Real(trail) = came_from {
+                draw_trail(
                     GridCanvas::draw_img_at_translated,
                     grid_canvas,
                     graphics.snake_graphics.get_head(*id),
-                    curr_pos,
-                    prev_pos,
-                    translate_factor,
+                    trail,
+                    tick_timer,
+                    curr_ms,
                 );
             }
         }
@@ -218,16 +331,100 @@ impl Draw for GameData {
     }
 }
 
+// Names above heads, moving along with them.
+fn draw_names(
+    gc: &GridCanvas,
+    gd: &GameData,
+    roster: &HashMap<SnakeID, Player>,
+    tick_timer: &TickTimer,
+    curr_ms: u64,
+) {
+    for (id, came_from) in gd.came_from_heads.iter() {
+        if let (CameFrom::Real(trail), Some(p)) = (came_from, roster.get(id)) {
+            draw_trail(
+                GridCanvas::draw_text_at_translated,
+                gc,
+                p.name.as_str(),
+                trail,
+                tick_timer,
+                curr_ms,
+            );
+        }
+    }
+}
+
+// Everyone playing, best first, in the top right corner.
+fn draw_scoreboard(
+    canvas: &Canvas,
+    graphics: &Graphics,
+    gd: &GameData,
+    roster: &HashMap<SnakeID, Player>,
+) {
+    // the game data has the latest scores
+    let score = |p: &Player| gd.scores.get(&p.id).cloned().unwrap_or(p.score);
+
+    let mut players: Vec<&Player> = roster.values().collect();
+    players.sort_by_key(|p| (std::cmp::Reverse(score(p)), p.id));
+
+    let x = canvas.width() as i32 - 8;
+
+    for (k, p) in players.iter().enumerate() {
+        let color = match p.team {
+            Some(team) => graphics.snake_graphics.get_team_color(team),
+            None => p.color.as_str(),
+        };
+
+        let line = match p.ping {
+            Some(ms) => format!("{}  {}  {} ms", p.name, score(p), ms),
+            None => format!("{}  {}", p.name, score(p)),
+        };
+
+        let y = 20 + 18 * k as i32;
+        canvas.draw_text(&line, color, x, y, TextAlign::Right);
+    }
+}
+
+// The last chat messages in the bottom left corner, above what is being
+// typed.
+fn draw_chat(canvas: &Canvas, chat: &Chat) {
+    let x = 8;
+    let mut y = canvas.height() as i32 - 10;
+
+    if let Some(ref draft) = chat.draft {
+        let line = format!("> {}_", draft);
+        canvas.draw_text(&line, TEXT_COLOR, x, y, TextAlign::Left);
+    }
+
+    for (who, text) in chat.lines.iter().rev() {
+        y -= 18;
+        let line = format!("{}: {}", who, text);
+        canvas.draw_text(&line, TEXT_COLOR, x, y, TextAlign::Left);
+    }
+}
+
 fn on_key_down(state: &AppStatePtr) {
     stdweb::web::document().add_event_listener({
         let state = state.clone();
         move |ev: KeyDownEvent | {
             let state = &mut state.borrow_mut();
+
+            // while the chat is being typed in every key goes to it
+            let chat = state.chat().filter(|chat| chat.is_typing());
+            if let Some(chat) = chat {
+                ev.prevent_default();
+                if let Some(text) = chat.key(&ev.key()) {
+                    state.send_chat(text);
+                }
+                return;
+            }
+
             match ev.key().as_ref() {
                 "w" => state.give_direction(Direction::Up),
                 "a" => state.give_direction(Direction::Left),
                 "s" => state.give_direction(Direction::Down),
                 "d" => state.give_direction(Direction::Right),
+                " " => state.boost(),
+                "Enter" => { state.chat().map(Chat::start_typing); },
                 _ => state.input(ev),
             }
         }
@@ -248,20 +445,46 @@ fn game_loop(
 
         if st.should_tick_game(curr_ms) {
             st.tick_game();
-            tick_timer = TickTimer::new(curr_ms, TICKS_PER_SECOND);
+            tick_timer = TickTimer::new(curr_ms, st.tick_rate());
         }
 
+        let snake_id = st.snake_id();
+        let roster = st.roster().cloned();
+        let chat = st.chat().map(|chat| chat.clone());
+
         // draw
         st.game_data().map(|gd| {
+            let screen = canvas.clone();
             let canvas = canvas.clone();
-            let canvas = canvas.grid_canvas(gd.grid_data.rows, gd.grid_data.cols);
+            let mut canvas =
+                canvas.grid_canvas(gd.grid_data.rows, gd.grid_data.cols);
+
+            // the game data is centered on our head, so the camera only has
+            // to keep up with it while it moves
+            let head = snake_id.and_then(|id| gd.came_from_heads.get(&id));
+            if let Some(came_from) = head {
+                let (di, dj) =
+                    trail_lag(came_from.trail(), &tick_timer, curr_ms);
+                canvas.follow(di, dj);
+            }
+
             let graphics = graphics.clone();
             canvas.clear(BKG_COLOR);
             gd.draw(
                 &canvas,
                 graphics.borrow(),
-                tick_timer.percent_left(curr_ms)
+                &tick_timer,
+                curr_ms,
             );
+
+            if let Some(roster) = roster {
+                draw_names(&canvas, gd, &roster, &tick_timer, curr_ms);
+                draw_scoreboard(&screen, graphics.borrow(), gd, &roster);
+            }
+
+            if let Some(chat) = chat {
+                draw_chat(&screen, &chat);
+            }
         });
     }
 
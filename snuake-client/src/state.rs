@@ -1,17 +1,15 @@
 use stdweb::traits::*;
 use stdweb::web::IEventTarget;
-use stdweb::web::WebSocket;
+use stdweb::web::{SocketBinaryType, WebSocket};
 use stdweb::web::event::{
     KeyDownEvent,
     SocketOpenEvent,
     SocketCloseEvent,
     SocketErrorEvent,
+    SocketMessageData,
     SocketMessageEvent,
 };
 
-extern crate serde;
-use serde_json;
-
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -21,7 +19,7 @@ use saas::util::*;
 use saas::state::*;
 // use saas::entity::*;
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 
 // ++++++++++++
 // + AppState +
@@ -40,17 +38,100 @@ pub trait AppState {
 
     fn game_data(&mut self) -> Option<&GameData>;
 
+    fn snake_id(&self) -> Option<SnakeID>;
+
+    // How many times per second the game advances.
+    fn tick_rate(&self) -> u64;
+
+    // Everyone playing, when playing online.
+    fn roster(&self) -> Option<&HashMap<SnakeID, Player>>;
+
+    // The chat, when playing online. Messages starting with a / go to the
+    // server's console instead, /rcon <password> logs in to it.
+    fn chat(&mut self) -> Option<&mut Chat>;
+
+    fn send_chat(&mut self, text: String);
+
     fn give_direction(&mut self, dir: Direction);
+
+    fn boost(&mut self);
 }
 
 pub type AppStatePtr = Rc<RefCell<Box<AppState>>>;
 
+// ++++++++
+// + Chat +
+// ++++++++
+
+// how many chat messages are shown
+const CHAT_LINES: usize = 12;
+
+// The last messages and what the player is typing, if anything.
+#[derive(Clone, Default)]
+pub struct Chat {
+    // who said what, oldest first
+    pub lines: VecDeque<(String, String)>,
+    pub draft: Option<String>,
+}
+
+impl Chat {
+    pub fn is_typing(&self) -> bool {
+        self.draft.is_some()
+    }
+
+    pub fn start_typing(&mut self) {
+        self.draft = Some(String::new());
+    }
+
+    fn push(&mut self, who: String, text: String) {
+        if self.lines.len() == CHAT_LINES {
+            self.lines.pop_front();
+        }
+
+        self.lines.push_back((who, text));
+    }
+
+    // Handles a key pressed while typing, returns the message once it is
+    // done.
+    pub fn key(&mut self, key: &str) -> Option<String> {
+        let draft = self.draft.as_mut()?;
+
+        match key {
+            "Enter" => self.draft.take().filter(|t| !t.trim().is_empty()),
+
+            "Escape" => {
+                self.draft = None;
+                None
+            },
+
+            "Backspace" => {
+                draft.pop();
+                None
+            },
+
+            // named keys, such as Shift, are longer than one character
+            _ if key.chars().count() == 1
+                && draft.chars().count() < MAX_CHAT_LEN =>
+            {
+                draft.push_str(key);
+                None
+            },
+
+            _ => None,
+        }
+    }
+}
+
 // ++++++++++++++++
 // + OfflineState +
 // ++++++++++++++++
 
+// how far the player sees around their snake when playing offline
+const VIEW_RADIUS: usize = 12;
+
 pub struct OfflineState {
     is_running: bool,
+    should_step: bool,
     snake_id: SnakeID,
     game_state: GameState,
     game_data: Option<GameData>,
@@ -68,6 +149,7 @@ impl OfflineState {
 
         let st = OfflineState {
             is_running: false,
+            should_step: false,
             snake_id: snake_id,
             game_state: game_state,
             game_data: None,
@@ -86,7 +168,10 @@ impl AppState for OfflineState {
     }
 
     fn should_tick_game(&mut self, curr_ms: u64) -> bool {
-        if self.is_running && self.prev_ms + self.wait_ms <= curr_ms {
+        if self.game_state.is_paused() {
+            self.prev_ms = curr_ms;
+            self.should_step
+        } else if self.is_running && self.prev_ms + self.wait_ms <= curr_ms {
             self.prev_ms = curr_ms;
             true
         } else {
@@ -95,13 +180,34 @@ impl AppState for OfflineState {
     }
 
     fn tick_game(&mut self) {
-        self.game_state.tick();
-        self.game_data = self.game_state.get_game_data();
+        if self.should_step {
+            self.should_step = false;
+            self.game_state.step();
+        } else {
+            self.game_state.tick();
+        }
+
+        // keep showing the last tick while paused
+        if let Some(gd) = self.game_state.get_game_data() {
+            let head = self.game_state.snake(self.snake_id).unwrap().head();
+            self.game_data = Some(gd.window(head, VIEW_RADIUS));
+        }
     }
 
     fn input(&mut self, ev: KeyDownEvent) {
         match ev.key().as_ref() {
             "p" => { self.game_state.add_snake().unwrap(); },
+
+            "Escape" => {
+                if self.game_state.is_paused() {
+                    self.game_state.resume();
+                } else {
+                    self.game_state.pause();
+                }
+            },
+
+            "n" if self.game_state.is_paused() => self.should_step = true,
+
             _ => (),
         }
     }
@@ -110,10 +216,35 @@ impl AppState for OfflineState {
         self.game_data.as_ref()
     }
 
+    fn snake_id(&self) -> Option<SnakeID> {
+        Some(self.snake_id)
+    }
+
+    fn tick_rate(&self) -> u64 {
+        TICKS_PER_SECOND
+    }
+
+    fn roster(&self) -> Option<&HashMap<SnakeID, Player>> {
+        None
+    }
+
+    fn chat(&mut self) -> Option<&mut Chat> {
+        None
+    }
+
+    fn send_chat(&mut self, _text: String) {
+    }
+
     fn give_direction(&mut self, dir: Direction) {
         let id = self.snake_id;
         self.game_state.give_direction(id, dir).unwrap();
     }
+
+    fn boost(&mut self) {
+        let id = self.snake_id;
+        // fails when the snake is too short, which is fine
+        self.game_state.boost(id).ok();
+    }
 }
 
 // +++++++++++++++
@@ -124,13 +255,47 @@ impl AppState for OfflineState {
 enum State {
     Live,
     Connecting,
+    // the server turned us away
+    Rejected,
+}
+
+const CLIENT_NAME: &str = concat!("snuake-client ", env!("CARGO_PKG_VERSION"));
+
+// how far the prediction may run ahead of the server
+const MAX_PREDICTED_TICKS: u64 = 8;
+
+// The player's name is whatever comes after the # in the address of the
+// page, until they pick another one with the n key.
+fn name_from_page() -> String {
+    let name = js! {
+        return decodeURIComponent(window.location.hash.slice(1));
+    };
+
+    name.into_string().unwrap_or_default()
 }
 
 pub struct OnlineState {
     state: State,
     snake_id: Option<SnakeID>,
+    // until the server says otherwise
+    tick_rate: u64,
+    map_info: Option<MapInfo>,
+    encoding: Encoding,
+    // the tick of the last game data from the server
+    server_tick: Option<u64>,
+    server_data: Option<GameData>,
+    next_seq: Seq,
+    // Our snake as we think it is at predicted_tick, see
+    // OnlineState::reconcile. The commands the server has not applied yet
+    // are kept along with the tick they were given after.
+    predicted: Option<GameState>,
+    predicted_tick: u64,
+    pending: VecDeque<(Seq, u64, UserCmd)>,
+    // what is drawn, the server's data with our predicted snake in it
     game_data: Option<GameData>,
-    has_new_game_data: bool,
+    roster: HashMap<SnakeID, Player>,
+    chat: Chat,
+    prev_ms: u64,
     sock: Option<Rc<RefCell<WebSocket>>>,
     msgs: Rc<RefCell<VecDeque<ServerMsg>>>
 }
@@ -140,14 +305,108 @@ impl OnlineState {
         let st = OnlineState {
             state: State::Connecting,
             snake_id: None,
+            tick_rate: TICKS_PER_SECOND,
+            map_info: None,
+            encoding: Encoding::Json,
+            server_tick: None,
+            server_data: None,
+            next_seq: 0,
+            predicted: None,
+            predicted_tick: 0,
+            pending: VecDeque::new(),
             game_data: None,
-            has_new_game_data: false,
+            roster: HashMap::new(),
+            chat: Chat::default(),
+            prev_ms: 0,
             sock: None,
             msgs: Rc::new(RefCell::new(VecDeque::new()))
         };
 
         Rc::new(RefCell::new(Box::new(st)))
     }
+
+    fn send(&self, msg: &ClientMsg) {
+        let sock = match self.sock {
+            Some(ref sock) => sock.borrow_mut(),
+            None => return,
+        };
+
+        match self.encoding.encode(msg) {
+            Some(Frame::Text(s)) => sock.send_text(&s).ok(),
+            Some(Frame::Binary(bytes)) => sock.send_bytes(&bytes).ok(),
+            None => None,
+        };
+    }
+
+    // Sends cmd to the server and applies it to our prediction right away.
+    fn send_cmd(&mut self, cmd: UserCmd) {
+        let seq = self.next_seq;
+        let msg = ClientMsg::UserCmd {
+            seq: seq,
+            tick: self.server_tick.unwrap_or(0),
+            cmd: cmd,
+        };
+
+        self.next_seq += 1;
+        self.send(&msg);
+
+        if let (Some(st), Some(id)) = (self.predicted.as_mut(), self.snake_id) {
+            apply_cmd(st, id, cmd);
+        }
+
+        self.pending.push_back((seq, self.predicted_tick, cmd));
+    }
+
+    // Starts the prediction over from what the server says the game was
+    // like after tick, replaying the commands it had not applied by then.
+    fn reconcile(&mut self, tick: u64, ack: Option<Seq>, gd: GameData) {
+        if let Some(ack) = ack {
+            while self.pending.front().map_or(false, |(seq, ..)| *seq <= ack) {
+                self.pending.pop_front();
+            }
+        }
+
+        self.predicted_tick = self
+            .predicted_tick
+            .max(tick)
+            .min(tick + MAX_PREDICTED_TICKS);
+
+        self.predicted = match (self.snake_id, self.map_info.as_ref()) {
+            (Some(id), Some(info)) => {
+                GameState::predicting(&gd, id, info.rows, info.cols)
+            }
+            _ => None,
+        };
+
+        if let (Some(st), Some(id)) = (self.predicted.as_mut(), self.snake_id) {
+            let mut curr_tick = tick;
+
+            for (_, cmd_tick, cmd) in self.pending.iter() {
+                while curr_tick < *cmd_tick.min(&self.predicted_tick) {
+                    st.tick();
+                    curr_tick += 1;
+                }
+
+                apply_cmd(st, id, *cmd);
+            }
+
+            while curr_tick < self.predicted_tick {
+                st.tick();
+                curr_tick += 1;
+            }
+        }
+
+        self.server_data = Some(gd);
+    }
+}
+
+// Commands fail for snakes that are dead or too short, which the server
+// will tell us about.
+fn apply_cmd(st: &mut GameState, id: SnakeID, cmd: UserCmd) {
+    match cmd {
+        UserCmd::Direction(dir) => st.give_direction(id, dir).ok(),
+        UserCmd::Boost => st.boost(id).ok(),
+    };
 }
 
 impl AppState for OnlineState {
@@ -159,16 +418,24 @@ impl AppState for OnlineState {
             console!(log, "{:?}", err);
         }).unwrap();
 
+        sock.set_binary_type(SocketBinaryType::ArrayBuffer);
         let sock = Rc::new(RefCell::new(sock));
 
         sock.borrow_mut().add_event_listener({
             let sock = sock.clone();
 
             move |ev: SocketOpenEvent| {
-                let msg = ClientMsg::Join;
-                let s = serde_json::to_string_pretty(&msg).unwrap();
-
-                sock.borrow_mut().send_text(&s).unwrap();
+                let msg = ClientMsg::Hello {
+                    protocol_version: PROTOCOL_VERSION,
+                    client_name: CLIENT_NAME.to_string(),
+                    player_name: name_from_page(),
+                    capabilities: vec![BINCODE.to_string()],
+                };
+
+                // the handshake is always JSON
+                if let Some(Frame::Text(s)) = Encoding::Json.encode(&msg) {
+                    sock.borrow_mut().send_text(&s).unwrap();
+                }
 
                 console!(log, "Socket open!");
                 console!(log, "{:?}", ev);
@@ -190,12 +457,20 @@ impl AppState for OnlineState {
             move |ev: SocketMessageEvent| {
                 let msgs = &mut msgs.borrow_mut();
 
-                ev.data().into_text().map(|msg| {
-                    let msg = msg.as_bytes();
-                    serde_json::from_slice(msg)
-                        .map(|msg| msgs.push_back(msg))
-                        .unwrap();
-                });
+                let frame = match ev.data() {
+                    SocketMessageData::Text(s) => Frame::Text(s),
+
+                    SocketMessageData::ArrayBuffer(buf) => {
+                        Frame::Binary(Vec::from(buf))
+                    },
+
+                    SocketMessageData::Blob(_) => return,
+                };
+
+                match frame.decode() {
+                    Some(msg) => msgs.push_back(msg),
+                    None => console!(log, "error @ init: bad message"),
+                }
             }
         });
 
@@ -203,13 +478,31 @@ impl AppState for OnlineState {
     }
 
     fn tick(&mut self) {
-        let st = self.state;
-        let mut msgs = self.msgs.borrow_mut();
+        let msgs = self.msgs.clone();
+        let mut msgs = msgs.borrow_mut();
 
         while let Some(msg) = msgs.pop_front() {
-            match st {
+            match self.state {
                 State::Connecting => {
                     match msg {
+                        ServerMsg::Welcome {
+                            server_version,
+                            tick_rate,
+                            map_info,
+                            capabilities,
+                            ..
+                        } => {
+                            console!(log, "Welcome to", server_version);
+                            self.tick_rate = tick_rate;
+                            self.map_info = Some(map_info);
+                            self.encoding = Encoding::negotiated(&capabilities);
+                        },
+
+                        ServerMsg::Rejected(reason) => {
+                            console!(log, "Rejected:", reason);
+                            self.state = State::Rejected;
+                        },
+
                         ServerMsg::NewID(id) => {
                             self.snake_id = Some(id);
                             self.state = State::Live;
@@ -221,42 +514,188 @@ impl AppState for OnlineState {
 
                 State::Live => {
                     match msg {
-                        ServerMsg::GameData(gd) => {
-                            self.game_data = Some(gd);
-                            self.has_new_game_data = true;
+                        ServerMsg::GameData { tick, ack, game_data } => {
+                            let prev = self.server_tick.unwrap_or(tick);
+                            if prev + 1 < tick {
+                                let missed = tick - prev - 1;
+                                console!(log, "Missed updates:", missed as u32);
+                            }
+
+                            self.server_tick = Some(tick);
+                            self.reconcile(tick, ack, game_data);
+                        },
+
+                        ServerMsg::Ping(u) => self.send(&ClientMsg::Pong(u)),
+
+                        ServerMsg::Roster(players) => {
+                            self.roster = players
+                                .into_iter()
+                                .map(|p| (p.id, p))
+                                .collect();
+                        },
+
+                        ServerMsg::RosterChange(RosterChange::Joined(p)) => {
+                            self.roster.insert(p.id, p);
+                        },
+
+                        ServerMsg::RosterChange(RosterChange::Left(id)) => {
+                            self.roster.remove(&id);
+                        },
+
+                        ServerMsg::RosterChange(
+                            RosterChange::Renamed(id, name)
+                        ) => {
+                            if let Some(p) = self.roster.get_mut(&id) {
+                                p.name = name;
+                            }
+                        },
+
+                        ServerMsg::Chat { from, text, .. } => {
+                            let who = match from {
+                                Some(id) => self
+                                    .roster
+                                    .get(&id)
+                                    .map(|p| p.name.clone())
+                                    .unwrap_or_else(|| format!("{}", id)),
+
+                                None => "*".to_string(),
+                            };
+
+                            self.chat.push(who, text);
+                        },
+
+                        ServerMsg::ConsoleOutput(out) => {
+                            for line in out.lines() {
+                                let who = "console".to_string();
+                                self.chat.push(who, line.to_string());
+                            }
+                        },
+
+                        // the server started over, with a new snake for us
+                        ServerMsg::NewID(id) => {
+                            self.snake_id = Some(id);
+                            self.server_tick = None;
+                            self.predicted = None;
+                            self.predicted_tick = 0;
+                            self.pending.clear();
+                        },
+
+                        ServerMsg::Rejected(reason) => {
+                            console!(log, "Kicked:", reason);
+                            self.state = State::Rejected;
                         },
 
                         _ => (),
                     }
-                }
+                },
+
+                State::Rejected => (),
             }
         }
     }
 
-    fn should_tick_game(&mut self, _curr_ms: u64) -> bool {
-        self.has_new_game_data
+    // The prediction keeps its own time between updates from the server.
+    fn should_tick_game(&mut self, curr_ms: u64) -> bool {
+        let wait_ms = 1000 / self.tick_rate;
+
+        if self.server_tick.is_some() && self.prev_ms + wait_ms <= curr_ms {
+            self.prev_ms = curr_ms;
+            true
+        } else {
+            false
+        }
     }
 
     fn tick_game(&mut self) {
-        self.has_new_game_data = false;
+        let server_tick = match self.server_tick {
+            Some(tick) => tick,
+            None => return,
+        };
+
+        // stops when the server goes quiet, e.g. because it is paused
+        let mut predicted_data = None;
+        if self.predicted_tick < server_tick + MAX_PREDICTED_TICKS {
+            self.predicted_tick += 1;
+
+            if let Some(st) = self.predicted.as_mut() {
+                st.tick();
+                predicted_data = st.get_game_data();
+            }
+        }
+
+        let mut gd = match self.server_data {
+            Some(ref gd) => gd.clone(),
+            None => return,
+        };
+
+        if let (Some(predicted), Some(id)) = (predicted_data, self.snake_id) {
+            gd.overlay(&predicted, id);
+        }
+
+        self.game_data = Some(gd);
     }
 
-    fn input(&mut self, _ev: KeyDownEvent) {  }
+    fn input(&mut self, ev: KeyDownEvent) {
+        match ev.key().as_ref() {
+            "n" => {
+                let name = js! { return prompt("Your name:"); };
+                if let Some(name) = name.into_string() {
+                    self.send(&ClientMsg::Rename(name));
+                }
+            },
+
+            _ => (),
+        }
+    }
 
     fn game_data(&mut self) -> Option<&GameData> {
         self.game_data.as_ref()
     }
 
+    fn snake_id(&self) -> Option<SnakeID> {
+        self.snake_id
+    }
+
+    fn tick_rate(&self) -> u64 {
+        self.tick_rate
+    }
+
+    fn roster(&self) -> Option<&HashMap<SnakeID, Player>> {
+        Some(&self.roster)
+    }
+
+    fn chat(&mut self) -> Option<&mut Chat> {
+        Some(&mut self.chat)
+    }
+
+    fn send_chat(&mut self, text: String) {
+        if let State::Live = self.state {
+            if text.starts_with("/rcon ") {
+                let password = text["/rcon ".len()..].trim().to_string();
+                self.send(&ClientMsg::RconAuth(password));
+            } else if text.starts_with('/') {
+                self.send(&ClientMsg::ConsoleCmd(text[1..].to_string()));
+            } else {
+                self.send(&ClientMsg::Chat { text: text });
+            }
+        }
+    }
+
     fn give_direction(&mut self, dir: Direction) {
         let st = self.state;
 
         match st {
-            State::Live => {
-                let cmd = UserCmd::Direction(dir);
-                let msg = ClientMsg::UserCmd(cmd);
-                let s = serde_json::to_string_pretty(&msg).unwrap();
-                self.sock.as_ref().map(|sock| sock.borrow_mut().send_text(&s));
-            },
+            State::Live => self.send_cmd(UserCmd::Direction(dir)),
+
+            _ => (),
+        }
+    }
+
+    fn boost(&mut self) {
+        let st = self.state;
+
+        match st {
+            State::Live => self.send_cmd(UserCmd::Boost),
 
             _ => (),
         }
@@ -0,0 +1,214 @@
+use snuake_shared::SnakeID;
+
+// ++++++++++++
+// + Commands +
+// ++++++++++++
+
+// Who is running a command. Commands typed into the server itself run as
+// Admin, players are Anonymous until they give the rcon password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Privilege {
+    Anonymous,
+    Admin,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Status,
+    Kick(SnakeID),
+    Map(String),
+    Restart,
+    Cvars,
+    Set(String, String),
+    Say(String),
+    AddBot,
+    Pause,
+    Resume,
+    Step,
+    Mute(SnakeID),
+    Unmute(SnakeID),
+    Mutes,
+}
+
+// what Command::Help prints
+pub const HELP: &str = "\
+help                 this text
+status               the map and who is playing
+kick <id>            disconnects a player
+map <name>           starts over on another map
+restart              starts over on the same map
+cvars                the settings and what they do
+set <cvar> <value>   changes a setting
+say <msg>            talks to everyone as the server
+addbot               adds a snake that steers itself
+pause, resume, step  stops, starts and steps the game
+mute <id>            keeps a player from chatting until they leave
+unmute <id>          lets them chat again
+mutes                who is muted";
+
+impl Command {
+    // The words after the command's name, if any, are its arguments. Fails
+    // with a message for whoever typed the command.
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, rest) = match line.find(char::is_whitespace) {
+            Some(k) => (&line[..k], line[k..].trim()),
+            None => (line, ""),
+        };
+
+        let args: Vec<&str> = rest.split_whitespace().collect();
+
+        let id = |args: &[&str]| match args {
+            [id] => id
+                .parse()
+                .map_err(|_| format!("{} is not a snake id", id)),
+
+            _ => Err(format!("usage: {} <id>", name)),
+        };
+
+        match (name, args.as_slice()) {
+            ("help", []) => Ok(Command::Help),
+            ("status", []) => Ok(Command::Status),
+            ("kick", args) => id(args).map(Command::Kick),
+            ("map", [map]) => Ok(Command::Map(map.to_string())),
+            ("restart", []) => Ok(Command::Restart),
+
+            ("cvars", []) => Ok(Command::Cvars),
+
+            ("set", [cvar, value]) => {
+                Ok(Command::Set(cvar.to_string(), value.to_string()))
+            }
+
+            ("say", args) if !args.is_empty() => {
+                Ok(Command::Say(rest.to_string()))
+            }
+
+            ("addbot", []) => Ok(Command::AddBot),
+            ("pause", []) => Ok(Command::Pause),
+            ("resume", []) => Ok(Command::Resume),
+            ("step", []) => Ok(Command::Step),
+            ("mute", args) => id(args).map(Command::Mute),
+            ("unmute", args) => id(args).map(Command::Unmute),
+            ("mutes", []) => Ok(Command::Mutes),
+            ("", _) => Err("no command given".to_string()),
+
+            ("map", _) => Err("usage: map <name>".to_string()),
+            ("set", _) => Err("usage: set <cvar> <value>".to_string()),
+            ("say", _) => Err("usage: say <msg>".to_string()),
+
+            ("help", _)
+            | ("status", _)
+            | ("restart", _)
+            | ("cvars", _)
+            | ("addbot", _)
+            | ("pause", _)
+            | ("resume", _)
+            | ("step", _)
+            | ("mutes", _) => Err(format!("{} takes no arguments", name)),
+
+            (name, _) => Err(format!("unknown command {}, try help", name)),
+        }
+    }
+
+    // The least privilege needed to run the command. Only commands that
+    // just look are open to everyone.
+    pub fn privilege(&self) -> Privilege {
+        match self {
+            Command::Help | Command::Status | Command::Cvars => {
+                Privilege::Anonymous
+            }
+            _ => Privilege::Admin,
+        }
+    }
+}
+
+// +++++++++
+// + Tests +
+// +++++++++
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Command {
+        Command::parse(line).unwrap()
+    }
+
+    fn error(line: &str) -> String {
+        Command::parse(line).unwrap_err()
+    }
+
+    // whether an anonymous player gets to run the command, see core
+    fn is_open(line: &str) -> bool {
+        parse(line).privilege() <= Privilege::Anonymous
+    }
+
+    #[test]
+    fn parse_every_command() {
+        assert_eq!(parse("help"), Command::Help);
+        assert_eq!(parse("status"), Command::Status);
+        assert_eq!(parse("kick 3"), Command::Kick(3));
+        assert_eq!(parse("map arena"), Command::Map("arena".to_string()));
+        assert_eq!(parse("restart"), Command::Restart);
+        assert_eq!(parse("cvars"), Command::Cvars);
+        assert_eq!(
+            parse("set cols 30"),
+            Command::Set("cols".to_string(), "30".to_string()),
+        );
+        assert_eq!(parse("say hi there"), Command::Say("hi there".to_string()));
+        assert_eq!(parse("addbot"), Command::AddBot);
+        assert_eq!(parse("pause"), Command::Pause);
+        assert_eq!(parse("resume"), Command::Resume);
+        assert_eq!(parse("step"), Command::Step);
+        assert_eq!(parse("mute 1"), Command::Mute(1));
+        assert_eq!(parse("unmute 1"), Command::Unmute(1));
+        assert_eq!(parse("mutes"), Command::Mutes);
+    }
+
+    #[test]
+    fn parse_ignores_extra_whitespace() {
+        assert_eq!(parse("  kick   3 "), Command::Kick(3));
+        assert_eq!(parse("say  a  b "), Command::Say("a  b".to_string()));
+    }
+
+    #[test]
+    fn parse_bad_arity() {
+        assert_eq!(error("kick"), "usage: kick <id>");
+        assert_eq!(error("mute 1 2"), "usage: mute <id>");
+        assert_eq!(error("unmute"), "usage: unmute <id>");
+        assert_eq!(error("map"), "usage: map <name>");
+        assert_eq!(error("map a b"), "usage: map <name>");
+        assert_eq!(error("set cols"), "usage: set <cvar> <value>");
+        assert_eq!(error("say"), "usage: say <msg>");
+
+        for name in &[
+            "help", "status", "restart", "cvars", "addbot", "pause", "resume",
+            "step", "mutes",
+        ] {
+            let line = format!("{} now", name);
+            assert_eq!(error(&line), format!("{} takes no arguments", name));
+        }
+    }
+
+    #[test]
+    fn parse_bad_arguments() {
+        assert_eq!(error("kick bob"), "bob is not a snake id");
+        assert_eq!(error("mute -1"), "-1 is not a snake id");
+        assert_eq!(error("jump"), "unknown command jump, try help");
+        assert_eq!(error(""), "no command given");
+        assert_eq!(error("   "), "no command given");
+    }
+
+    #[test]
+    fn anonymous_players_can_only_look() {
+        assert!(is_open("help"));
+        assert!(is_open("status"));
+        assert!(is_open("cvars"));
+
+        assert!(!is_open("kick 0"));
+        assert!(!is_open("restart"));
+        assert!(!is_open("set cols 30"));
+        assert!(!is_open("addbot"));
+    }
+}
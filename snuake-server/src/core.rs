@@ -1,8 +1,8 @@
 use snuake_shared::*;
 
-use std::collections::HashMap;
-use std::net::SocketAddr;
-use std::time::{Instant, Duration};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use futures::prelude::*;
 use tokio::sync::mpsc;
@@ -14,16 +14,32 @@ pub enum Event {
     Opened(SocketAddr, mpscUS<ServerMsg>),
     Closed(SocketAddr),
     Ping(SocketAddr, usize),
-    Join(SocketAddr),
+    // protocol version, client name, player name and capabilities
+    Hello(SocketAddr, u32, String, String, Vec<String>),
+    // a first message that is not a Hello this server can read, along with
+    // the protocol version it was sent in if that is known
+    BadHello(SocketAddr, Option<u32>),
+    Rename(SocketAddr, String),
+    Chat(SocketAddr, String),
+    Pong(SocketAddr, usize),
     CCmd(Option<SocketAddr>, String),
-    UCmd(SocketAddr, UserCmd),
+    RconAuth(SocketAddr, String),
+    // sequence number and the last tick the client had heard of
+    UCmd(SocketAddr, Seq, u64, UserCmd),
 }
 
-pub fn core() -> (mpscUS<Event>, impl Future<Item = (), Error = ()>) {
+// the optional features this server supports, see ClientMsg::Hello
+const CAPABILITIES: &[&str] = &[BINCODE];
+
+// The game is set up as given by the cvars, see cvar::CVARS.
+pub fn core(
+    cvars: Cvars,
+) -> (mpscUS<Event>, impl Future<Item = (), Error = ()>) {
 
     let (core_s, core_r) = mpsc::unbounded_channel();
 
-    let dur = Duration::from_millis(1000/TICKS_PER_SECOND);
+    let tick_rate = cvars.int(cvar::TICK_RATE) as u64;
+    let dur = Duration::from_millis(1000/tick_rate);
     let ticker = Interval::new(Instant::now(), dur)
         .map(|_|Event::Tick)
         .map_err(|_| ());
@@ -34,18 +50,262 @@ pub fn core() -> (mpscUS<Event>, impl Future<Item = (), Error = ()>) {
     (
         core_s,
         ticker.select(core_r)
-            .for_each(core_inner())
+            .for_each(core_inner(cvars))
     )
 }
 
 use saas;
+use saas::entity::{Kind, Phase};
+use saas::map::Map;
+use saas::state::GameState;
+use saas::util::{Direction, Timer};
+
+use crate::console::{Command, Privilege, HELP};
+use crate::cvar::{self, Cvars};
+
+// the map the server starts on
+const MAP: &str = "arena";
+
+// Players may send this many chat messages within CHAT_WINDOW_SECS.
+const CHAT_BURST: usize = 5;
+const CHAT_WINDOW_SECS: u64 = 10;
+
+// After this many wrong rcon passwords from an address it is locked out
+// until RCON_LOCKOUT_SECS have passed since the last one.
+const RCON_TRIES: usize = 3;
+const RCON_LOCKOUT_SECS: u64 = 60;
+
+// What the server knows about a player besides their snake.
+struct Profile {
+    name: String,
+    // in milliseconds
+    ping: Option<u64>,
+    // the last ServerMsg::Ping sent to the player and when
+    ping_sent: Option<(usize, Instant)>,
+    // when the player's last chat messages were sent, oldest first
+    chat_times: VecDeque<Instant>,
+}
+
+impl Profile {
+    fn new(name: String) -> Self {
+        Profile {
+            name: name,
+            ping: None,
+            ping_sent: None,
+            chat_times: VecDeque::with_capacity(CHAT_BURST),
+        }
+    }
+
+    // Whether the player may chat now, counting it if so.
+    fn may_chat(&mut self) -> bool {
+        let now = Instant::now();
+        let window = Duration::from_secs(CHAT_WINDOW_SECS);
+
+        while self
+            .chat_times
+            .front()
+            .map_or(false, |t| now.duration_since(*t) >= window)
+        {
+            self.chat_times.pop_front();
+        }
+
+        if self.chat_times.len() < CHAT_BURST {
+            self.chat_times.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn send(ch_s: &mpscUS<ServerMsg>, msg: ServerMsg) {
+    tokio::spawn(ch_s.clone().send(msg).map(|_| ()).map_err(|_| ()));
+}
+
+fn broadcast(
+    connections: &HashMap<SocketAddr, mpscUS<ServerMsg>>,
+    msg: ServerMsg,
+) {
+    for ch_s in connections.values() {
+        send(ch_s, msg.clone());
+    }
+}
+
+// Names are cut short and stripped of control characters. Players without
+// a name are named after their snake.
+fn clean_name(name: &str, id: SnakeID) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LEN)
+        .collect();
+
+    if name.is_empty() {
+        format!("snakie {}", id)
+    } else {
+        name
+    }
+}
+
+// Cut short and stripped of control characters, None if nothing is left.
+fn clean_chat(text: &str) -> Option<String> {
+    let text: String = text
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_CHAT_LEN)
+        .collect();
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+fn chat(from: Option<SnakeID>, text: String) -> ServerMsg {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1000 + d.subsec_millis() as u64)
+        .unwrap_or(0);
+
+    ServerMsg::Chat {
+        from: from,
+        text: text,
+        ts: ts,
+    }
+}
+
+fn player(game: &GameState, id: SnakeID, profile: &Profile) -> Player {
+    Player {
+        id: id,
+        name: profile.name.clone(),
+        color: PLAYER_COLORS[id as usize % PLAYER_COLORS.len()].to_string(),
+        team: game.get_team(id),
+        score: game.get_score(id).unwrap_or(0),
+        ping: profile.ping,
+    }
+}
+
+fn roster(
+    game: &GameState,
+    snake_ids: &HashMap<SocketAddr, SnakeID>,
+    profiles: &HashMap<SocketAddr, Profile>,
+    bots: &HashMap<SnakeID, Profile>,
+) -> Vec<Player> {
+    let bots = bots.iter().map(|(id, profile)| player(game, *id, profile));
+
+    snake_ids
+        .iter()
+        .filter_map(|(addr, id)| {
+            profiles.get(addr).map(|profile| player(game, *id, profile))
+        })
+        .chain(bots)
+        .collect()
+}
+
+// One line per player, for the status command.
+fn status(players: &[Player]) -> String {
+    let mut lines = vec![format!(
+        "{:>4}  {:<16}  {:>6}  {}",
+        "id", "name", "score", "ping"
+    )];
+
+    for p in players {
+        let ping = match p.ping {
+            Some(ms) => format!("{}ms", ms),
+            None => "-".to_string(),
+        };
+
+        lines.push(format!(
+            "{:>4}  {:<16}  {:>6}  {}",
+            p.id, p.name, p.score, ping
+        ));
+    }
+
+    lines.join("\n")
+}
+
+// The arena's size is up to the cvars, other maps have their own.
+fn load_map(name: &str, cvars: &Cvars) -> Option<Map> {
+    match name {
+        "arena" => {
+            Some(Map::empty(cvars.usize(cvar::ROWS), cvars.usize(cvar::COLS)))
+        }
+        name => Map::builtin(name),
+    }
+}
+
+fn new_game(map: Map, cvars: &Cvars) -> GameState {
+    let prop_spawn_ticks = cvars.usize(cvar::PROP_SPAWN_TICKS);
+
+    GameState::builder()
+        .with_map(map)
+        .with_prop_spawn_timer(Timer::new(prop_spawn_ticks))
+        .with_spawn_ticks(cvars.usize(cvar::SPAWN_TICKS))
+        .with_death_ticks(cvars.usize(cvar::DEATH_TICKS))
+        .with_decay_on_remove(cvars.bool(cvar::DECAY_ON_REMOVE))
+        .build()
+}
+
+// Bots keep going until something is in their way, then turn towards a
+// free cell if there is one.
+fn steer_bot(game: &mut GameState, id: SnakeID) {
+    let (head, dir) = match game.snake(id) {
+        Some(ref sn) if sn.is_alive() => (sn.head(), sn.direction()),
+        _ => return,
+    };
+
+    let turn = {
+        let is_free = |dir: &Direction| {
+            let (rows, cols) = ((0, game.rows()), (0, game.cols()));
+            let idx = head.neighbor(dir).wrap(rows, cols);
+
+            match game.tag_at(idx).kind {
+                Kind::None | Kind::Prop(_) | Kind::Hazard(Phase::Safe) => true,
+                _ => false,
+            }
+        };
+
+        if is_free(&dir) {
+            return;
+        }
+
+        [Direction::Up, Direction::Down, Direction::Left, Direction::Right]
+            .iter()
+            .cloned()
+            .find(|turn| *turn != dir.opposite() && is_free(turn))
+    };
+
+    if let Some(turn) = turn {
+        game.give_direction(id, turn).unwrap();
+    }
+}
+
+fn core_inner(mut cvars: Cvars) -> impl FnMut(Event) -> Result<(), ()> {
+    cvars.start();
+    let tick_rate = cvars.int(cvar::TICK_RATE) as u64;
 
-fn core_inner() -> impl FnMut(Event) -> Result<(), ()> {
     let mut connections = HashMap::new();
-    let mut snake_ids = HashMap::new();
-    let mut snake_game = saas::state::GameState::builder()
-            .with_dimensions(20,20)
-            .build();
+    let mut snake_ids: HashMap<SocketAddr, SnakeID> = HashMap::new();
+    // the last user command applied for each connection
+    let mut acks = HashMap::new();
+    let mut profiles: HashMap<SocketAddr, Profile> = HashMap::new();
+    // connections that can not chat, until they close
+    let mut muted: HashSet<SocketAddr> = HashSet::new();
+    // connections that gave the rcon password
+    let mut admins: HashSet<SocketAddr> = HashSet::new();
+    // wrong rcon passwords and when the last one came, counted per address
+    // so that reconnecting does not help
+    let mut rcon_failures: HashMap<IpAddr, (usize, Instant)> = HashMap::new();
+    // the roster is sent and pings are measured once a second
+    let mut ticks_to_roster = 0;
+    let mut next_ping = 0;
+    // snakes added by the addbot command
+    let mut bots: HashMap<SnakeID, Profile> = HashMap::new();
+    let mut map_name = MAP.to_string();
+    let mut snake_game = new_game(load_map(MAP, &cvars).unwrap(), &cvars);
 
     move |event| {
         match event {
@@ -58,45 +318,575 @@ fn core_inner() -> impl FnMut(Event) -> Result<(), ()> {
                 if let Some(s) = snake_ids.remove(&addr) {
                     println!("Removing snakie {:?}", s);
                     snake_game.remove_snake(s).unwrap();
+                    let change = RosterChange::Left(s);
+                    broadcast(&connections, ServerMsg::RosterChange(change));
                 }
+                acks.remove(&addr);
+                profiles.remove(&addr);
+                admins.remove(&addr);
+                muted.remove(&addr);
                 connections.remove(&addr);
             }
-            Event::CCmd(addr, s) => {
-                println!("CCmd from {:?}: {:?}", addr, s);
+            Event::CCmd(addr, line) => {
+                println!("CCmd from {:?}: {:?}", addr, line);
+
+                // commands typed into the server itself run as Admin
+                let privilege = match addr {
+                    Some(addr) if admins.contains(&addr) => Privilege::Admin,
+                    Some(_) => Privilege::Anonymous,
+                    None => Privilege::Admin,
+                };
+
+                let cmd = match Command::parse(&line) {
+                    Ok(ref cmd) if privilege < cmd.privilege() => {
+                        Err("you are not allowed to do that".to_string())
+                    }
+
+                    cmd => cmd,
+                };
+
+                let out = match cmd {
+                    Err(e) => e,
+
+                    Ok(Command::Help) => HELP.to_string(),
+
+                    Ok(Command::Status) => {
+                        let players =
+                            roster(&snake_game, &snake_ids, &profiles, &bots);
+                        format!(
+                            "map {}, tick {}, {} players\n{}",
+                            map_name,
+                            snake_game.ticks(),
+                            players.len(),
+                            status(&players),
+                        )
+                    }
+
+                    Ok(Command::Kick(id)) => {
+                        let kicked = snake_ids
+                            .iter()
+                            .find(|(_, sid)| **sid == id)
+                            .map(|(addr, _)| *addr);
+
+                        if let Some(addr) = kicked {
+                            // the connection closes once its last sender is
+                            // gone
+                            if let Some(ws_s) = connections.remove(&addr) {
+                                let reason = "kicked".to_string();
+                                send(&ws_s, ServerMsg::Rejected(reason));
+                            }
+                            snake_ids.remove(&addr);
+                            acks.remove(&addr);
+                            profiles.remove(&addr);
+                            admins.remove(&addr);
+                            muted.remove(&addr);
+                        }
+
+                        if kicked.is_some() || bots.remove(&id).is_some() {
+                            snake_game.remove_snake(id).unwrap();
+                            let change = RosterChange::Left(id);
+                            broadcast(
+                                &connections,
+                                ServerMsg::RosterChange(change),
+                            );
+                            format!("kicked snakie {}", id)
+                        } else {
+                            format!("no snakie {}", id)
+                        }
+                    }
+
+                    Ok(Command::Map(ref name))
+                        if load_map(name, &cvars).is_none() =>
+                    {
+                        format!(
+                            "unknown map {}, try one of {}",
+                            name,
+                            Map::builtin_names().join(", "),
+                        )
+                    }
+
+                    Ok(cmd @ Command::Map(_)) | Ok(cmd @ Command::Restart) => {
+                        // restarting is starting over on the same map
+                        if let Command::Map(name) = cmd {
+                            map_name = name;
+                        }
+
+                        for name in cvars.new_round() {
+                            let text =
+                                format!("{} is now {}", name, cvars.show(name));
+                            broadcast(&connections, chat(None, text));
+                        }
+
+                        let map = load_map(&map_name, &cvars).unwrap();
+                        snake_game = new_game(map, &cvars);
+
+                        // everyone starts over with a new snake, those who
+                        // do not fit on the new map are let go
+                        let mut unplaced = Vec::new();
+                        snake_ids.retain(|addr, id| {
+                            match snake_game.add_snake() {
+                                Some(id2) => {
+                                    *id = id2;
+                                    if let Some(ws_s) = connections.get(addr) {
+                                        send(ws_s, ServerMsg::NewID(id2));
+                                    }
+                                    true
+                                }
+
+                                None => {
+                                    unplaced.push(*addr);
+                                    false
+                                }
+                            }
+                        });
+
+                        for addr in unplaced.iter() {
+                            // the connection closes once its last sender is
+                            // gone
+                            if let Some(ws_s) = connections.remove(addr) {
+                                let reason = format!("no room on {}", map_name);
+                                send(&ws_s, ServerMsg::Rejected(reason));
+                            }
+                            acks.remove(addr);
+                            profiles.remove(addr);
+                            admins.remove(addr);
+                            muted.remove(addr);
+                        }
+
+                        let old_bots =
+                            std::mem::replace(&mut bots, HashMap::new());
+                        let mut dropped = 0;
+                        for (_, profile) in old_bots {
+                            match snake_game.add_snake() {
+                                Some(id) => {
+                                    bots.insert(id, profile);
+                                }
+                                None => dropped += 1,
+                            }
+                        }
+
+                        let players =
+                            roster(&snake_game, &snake_ids, &profiles, &bots);
+                        broadcast(&connections, ServerMsg::Roster(players));
+
+                        let mut out = format!("playing on {}", map_name);
+                        if !unplaced.is_empty() {
+                            out += &format!(
+                                ", no room for {} players",
+                                unplaced.len()
+                            );
+                        }
+                        if 0 < dropped {
+                            out += &format!(", dropped {} bots", dropped);
+                        }
+                        out
+                    }
+
+                    Ok(Command::Cvars) => cvars.describe(),
+
+                    Ok(Command::Set(name, value)) => {
+                        match cvars.set(&name, &value) {
+                            Ok(true) => {
+                                let text = format!(
+                                    "{} is now {}",
+                                    name,
+                                    cvars.show(&name),
+                                );
+                                let msg = chat(None, text.clone());
+                                broadcast(&connections, msg);
+                                text
+                            }
+                            Ok(false) => {
+                                format!("{} changes on the next round", name)
+                            }
+                            Err(e) => e,
+                        }
+                    }
+
+                    Ok(Command::Say(text)) => {
+                        broadcast(&connections, chat(None, text.clone()));
+                        format!("* {}", text)
+                    }
+
+                    Ok(Command::AddBot) => match snake_game.add_snake() {
+                        Some(id) => {
+                            let profile = Profile::new(format!("bot {}", id));
+
+                            let change = RosterChange::Joined(
+                                player(&snake_game, id, &profile),
+                            );
+                            let msg = ServerMsg::RosterChange(change);
+                            broadcast(&connections, msg);
+                            bots.insert(id, profile);
+
+                            format!("added bot {}", id)
+                        }
+
+                        None => "no room for another bot".to_string(),
+                    },
+
+                    Ok(Command::Pause) => {
+                        snake_game.pause();
+                        "paused".to_string()
+                    }
+
+                    Ok(Command::Resume) => {
+                        snake_game.resume();
+                        "resumed".to_string()
+                    }
+
+                    Ok(Command::Step) => {
+                        snake_game.step();
+                        "stepping one tick".to_string()
+                    }
+
+                    Ok(Command::Mute(id)) | Ok(Command::Unmute(id)) => {
+                        let player = snake_ids
+                            .iter()
+                            .find(|(_, sid)| **sid == id)
+                            .map(|(addr, _)| *addr);
+
+                        match (player, cmd) {
+                            (Some(addr), Ok(Command::Mute(_))) => {
+                                muted.insert(addr);
+                                format!("muted snakie {}", id)
+                            }
+                            (Some(addr), _) => {
+                                muted.remove(&addr);
+                                format!("unmuted snakie {}", id)
+                            }
+                            (None, _) => format!("no snakie {}", id),
+                        }
+                    }
+
+                    Ok(Command::Mutes) => {
+                        let ids: Vec<SnakeID> = muted
+                            .iter()
+                            .filter_map(|addr| snake_ids.get(addr))
+                            .cloned()
+                            .collect();
+
+                        let players =
+                            roster(&snake_game, &snake_ids, &profiles, &bots);
+                        let players: Vec<Player> = players
+                            .into_iter()
+                            .filter(|p| ids.contains(&p.id))
+                            .collect();
+
+                        if players.is_empty() {
+                            "nobody is muted".to_string()
+                        } else {
+                            status(&players)
+                        }
+                    }
+                };
+
+                match addr {
+                    Some(addr) => {
+                        if let Some(ws_s) = connections.get(&addr) {
+                            send(ws_s, ServerMsg::ConsoleOutput(out));
+                        }
+                    }
+                    None => println!("{}", out),
+                }
+            }
+            Event::RconAuth(addr, guess) => {
+                let ws_s = match connections.get(&addr) {
+                    Some(ws_s) => ws_s,
+                    None => return Ok(()),
+                };
+
+                let now = Instant::now();
+                let lockout = Duration::from_secs(RCON_LOCKOUT_SECS);
+
+                // older failures are forgotten once a lockout would be over
+                let failures = match rcon_failures.get(&addr.ip()) {
+                    Some((n, last)) if now.duration_since(*last) < lockout => {
+                        *n
+                    }
+                    _ => 0,
+                };
+
+                let password = cvars.str(cvar::RCON_PASSWORD);
+                let out = if password.is_empty() {
+                    "rcon is off"
+                } else if RCON_TRIES <= failures {
+                    "too many wrong passwords, try again later"
+                } else if check_password(password, &guess) {
+                    rcon_failures.remove(&addr.ip());
+                    admins.insert(addr);
+                    "rcon password accepted"
+                } else {
+                    rcon_failures.insert(addr.ip(), (failures + 1, now));
+                    "wrong rcon password"
+                };
+
+                println!("Rcon from {:?}: {}", addr, out);
+                send(ws_s, ServerMsg::ConsoleOutput(out.to_string()));
             }
             Event::Ping(addr, u) => {
                 println!("Ping from {:?}: {:?}", addr, u);
-                let ws_s = connections.get(&addr).unwrap().clone();
-                tokio::spawn(
-                    ws_s.send(ServerMsg::Pong(u)).map(|_| ()).map_err(|_| ()),
-                );
+                // rejected clients can still ping before they are closed
+                if let Some(ws_s) = connections.get(&addr) {
+                    let ws_s = ws_s.clone();
+                    tokio::spawn(
+                        ws_s.send(ServerMsg::Pong(u))
+                            .map(|_| ())
+                            .map_err(|_| ()),
+                    );
+                }
             }
-            Event::Join(addr) => {
-                println!("Authenticate from {:?}", addr);
-                let snake_id = snake_game.add_snake().unwrap();
+            Event::Hello(addr, version, client, name, capabilities) => {
+                println!("Hello from {:?}: {:?} ({})", addr, client, version);
+                let ws_s = match connections.get(&addr) {
+                    Some(ws_s) => ws_s.clone(),
+                    None => return Ok(()),
+                };
+
+                // nothing is sent before the player has a snake
+                let joined = if version != PROTOCOL_VERSION {
+                    Err(format!(
+                        "the server speaks protocol version {}, not {}",
+                        PROTOCOL_VERSION,
+                        version,
+                    ))
+                } else if snake_ids.contains_key(&addr) {
+                    Err("already joined".to_string())
+                } else {
+                    snake_game
+                        .add_snake()
+                        .ok_or_else(|| "the arena is full".to_string())
+                };
+
+                let snake_id = match joined {
+                    Ok(snake_id) => snake_id,
+                    Err(reason) => {
+                        println!("Rejected {:?}: {}", addr, reason);
+                        send(&ws_s, ServerMsg::Rejected(reason));
+                        // the connection closes once its last sender is gone
+                        connections.remove(&addr);
+                        profiles.remove(&addr);
+                        admins.remove(&addr);
+                        muted.remove(&addr);
+                        if let Some(s) = snake_ids.remove(&addr) {
+                            snake_game.remove_snake(s).unwrap();
+                            let change = RosterChange::Left(s);
+                            let msg = ServerMsg::RosterChange(change);
+                            broadcast(&connections, msg);
+                        }
+                        return Ok(());
+                    }
+                };
                 println!("Added snakie {:?}", snake_id);
-                let ws_s = connections.get(&addr).unwrap().clone();
-                tokio::spawn(
-                    ws_s.send(ServerMsg::NewID(snake_id)).map(|_| ()).map_err(|_| ()),
+
+                let capabilities = capabilities
+                    .into_iter()
+                    .filter(|c| CAPABILITIES.contains(&c.as_str()))
+                    .collect();
+
+                let welcome = ServerMsg::Welcome {
+                    server_version: env!("CARGO_PKG_VERSION").to_string(),
+                    protocol_version: PROTOCOL_VERSION,
+                    tick_rate: tick_rate,
+                    map_info: MapInfo {
+                        rows: snake_game.rows(),
+                        cols: snake_game.cols(),
+                        view_radius: cvars.usize(cvar::VIEW_RADIUS),
+                    },
+                    capabilities: capabilities,
+                };
+
+                let profile = Profile::new(clean_name(&name, snake_id));
+
+                // the new player hears of everyone after the welcome
+                let change = RosterChange::Joined(
+                    player(&snake_game, snake_id, &profile),
                 );
+                for (a, ch_s) in connections.iter() {
+                    if *a != addr {
+                        let change = change.clone();
+                        send(ch_s, ServerMsg::RosterChange(change));
+                    }
+                }
+
                 snake_ids.insert(addr, snake_id);
+                profiles.insert(addr, profile);
+                let players = roster(&snake_game, &snake_ids, &profiles, &bots);
+
+                // sent together so that the welcome always comes first
+                tokio::spawn(
+                    ws_s.send(welcome)
+                        .and_then(move |ws_s| {
+                            ws_s.send(ServerMsg::NewID(snake_id))
+                        })
+                        .and_then(move |ws_s| {
+                            ws_s.send(ServerMsg::Roster(players))
+                        })
+                        .map(|_| ())
+                        .map_err(|_| ()),
+                );
+            }
+            Event::BadHello(addr, version) => {
+                let reason = match version {
+                    Some(version) if version == PROTOCOL_VERSION => {
+                        "could not read the hello".to_string()
+                    }
+                    Some(version) => format!(
+                        "the server speaks protocol version {}, not {}",
+                        PROTOCOL_VERSION,
+                        version,
+                    ),
+                    None => format!(
+                        "the server speaks protocol version {}, update the \
+                         client",
+                        PROTOCOL_VERSION,
+                    ),
+                };
+
+                println!("Rejected {:?}: {}", addr, reason);
+                // the connection closes once its last sender is gone
+                if let Some(ws_s) = connections.remove(&addr) {
+                    send(&ws_s, ServerMsg::Rejected(reason));
+                }
+                profiles.remove(&addr);
+                admins.remove(&addr);
+                muted.remove(&addr);
+            }
+            Event::Rename(addr, name) => {
+                let id = match snake_ids.get(&addr) {
+                    Some(id) => *id,
+                    None => return Ok(()),
+                };
+
+                if let Some(profile) = profiles.get_mut(&addr) {
+                    profile.name = clean_name(&name, id);
+                    println!("{:?} is now {:?}", addr, profile.name);
+
+                    let name = profile.name.clone();
+                    let change = RosterChange::Renamed(id, name);
+                    broadcast(&connections, ServerMsg::RosterChange(change));
+                }
+            }
+            Event::Chat(addr, text) => {
+                // only players chat
+                let (id, profile) = match (
+                    snake_ids.get(&addr),
+                    profiles.get_mut(&addr),
+                ) {
+                    (Some(id), Some(profile)) => (*id, profile),
+                    _ => return Ok(()),
+                };
+
+                let text = match clean_chat(&text) {
+                    Some(text) => text,
+                    None => return Ok(()),
+                };
+
+                let ws_s = match connections.get(&addr) {
+                    Some(ws_s) => ws_s,
+                    None => return Ok(()),
+                };
+
+                if muted.contains(&addr) {
+                    send(ws_s, chat(None, "You are muted.".to_string()));
+                } else if !profile.may_chat() {
+                    send(ws_s, chat(None, "Slow down!".to_string()));
+                } else {
+                    println!("Chat from {:?}: {:?}", profile.name, text);
+                    broadcast(&connections, chat(Some(id), text));
+                }
+            }
+            Event::Pong(addr, u) => {
+                if let Some(profile) = profiles.get_mut(&addr) {
+                    match profile.ping_sent {
+                        Some((v, sent)) if u == v => {
+                            let rtt = sent.elapsed();
+                            let ms = rtt.as_secs() * 1000
+                                + rtt.subsec_millis() as u64;
+                            profile.ping = Some(ms);
+                            profile.ping_sent = None;
+                        }
+
+                        // an answer to an older ping, or made up
+                        _ => (),
+                    }
+                }
+            }
+            Event::UCmd(addr, seq, _, _)
+                if acks.get(&addr).map_or(false, |ack| seq <= *ack) =>
+            {
+                // commands arrive in order, so this one was applied already
+                println!("{:?}: dropped old command {}", addr, seq);
             }
-            Event::UCmd(addr, UserCmd::Direction(dir)) => {
-                println!("{:?}: got {:?}", addr, dir);
+            Event::UCmd(addr, seq, tick, UserCmd::Direction(dir)) => {
+                println!("{:?}: got {:?} after tick {}", addr, dir, tick);
+                acks.insert(addr, seq);
                 if let Some(sid) = snake_ids.get(&addr) {
                     snake_game.give_direction(*sid, dir).unwrap();
                 } else {
                     println!("User tried to stuff but has no snakie");
                 }
             }
+            Event::UCmd(addr, seq, _, UserCmd::Boost) => {
+                acks.insert(addr, seq);
+                if let Some(sid) = snake_ids.get(&addr) {
+                    // fails when the snake is too short to boost
+                    snake_game.boost(*sid).ok();
+                } else {
+                    println!("User tried to stuff but has no snakie");
+                }
+            }
             Event::Tick => {
+                for id in bots.keys() {
+                    steer_bot(&mut snake_game, *id);
+                }
                 snake_game.tick();
-                let gd = snake_game.get_game_data()
-                    .expect("Error: snake_game.game_data() = None!");
 
-                for ws_s in connections.values() {
+                // kept up while the game is paused, too
+                if ticks_to_roster == 0 {
+                    ticks_to_roster = tick_rate;
+
+                    let players =
+                        roster(&snake_game, &snake_ids, &profiles, &bots);
+                    broadcast(&connections, ServerMsg::Roster(players));
+
+                    for (addr, profile) in profiles.iter_mut() {
+                        if let Some(ws_s) = connections.get(addr) {
+                            send(ws_s, ServerMsg::Ping(next_ping));
+                            profile.ping_sent =
+                                Some((next_ping, Instant::now()));
+                            next_ping += 1;
+                        }
+                    }
+                }
+                ticks_to_roster -= 1;
+
+                // nothing happens while the game is paused
+                let gd = match snake_game.get_game_data() {
+                    Some(gd) => gd,
+                    None => return Ok(()),
+                };
+
+                // players without a snake look at the middle of the grid
+                let middle = saas::util::Index2D::new(
+                    snake_game.rows() / 2,
+                    snake_game.cols() / 2,
+                );
+
+                for (addr, ws_s) in connections.iter() {
+                    let center = snake_ids
+                        .get(addr)
+                        .and_then(|id| snake_game.snake(*id))
+                        .map(|sn| sn.head())
+                        .unwrap_or(middle);
+
                     let ws_s = ws_s.clone();
-                    let msg = ServerMsg::GameData(gd.clone());
+                    let gd = gd.window(center, cvars.usize(cvar::VIEW_RADIUS));
+                    let msg = ServerMsg::GameData {
+                        tick: snake_game.ticks(),
+                        ack: acks.get(addr).cloned(),
+                        game_data: gd,
+                    };
                     let future = ws_s.send(msg).map(|_| ()).map_err(|_| ());
                     tokio::spawn(future);
                 }
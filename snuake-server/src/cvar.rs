@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use snuake_shared::TICKS_PER_SECOND;
+
+// +++++++++
+// + Cvars +
+// +++++++++
+
+// The names of the cvars, for looking them up.
+pub const ROWS: &str = "rows";
+pub const COLS: &str = "cols";
+pub const TICK_RATE: &str = "tick_rate";
+pub const VIEW_RADIUS: &str = "view_radius";
+pub const PROP_SPAWN_TICKS: &str = "prop_spawn_ticks";
+pub const SPAWN_TICKS: &str = "spawn_ticks";
+pub const DEATH_TICKS: &str = "death_ticks";
+pub const DECAY_ON_REMOVE: &str = "decay_on_remove";
+pub const RCON_PASSWORD: &str = "rcon_password";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    // within min..=max
+    Int { min: i64, max: i64 },
+    Bool,
+    // a string that is never shown
+    Password,
+}
+
+// When a change takes effect. Changes made before the server starts
+// always take effect at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Apply {
+    Now,
+    // when the map is changed or restarted
+    NextRound,
+    // the cvar can not be changed once the server is running
+    AtStart,
+}
+
+pub struct Cvar {
+    pub name: &'static str,
+    pub ty: Type,
+    pub default: Value,
+    pub apply: Apply,
+    pub description: &'static str,
+}
+
+pub const CVARS: &[Cvar] = &[
+    Cvar {
+        name: ROWS,
+        ty: Type::Int { min: 4, max: 500 },
+        default: Value::Int(20),
+        apply: Apply::NextRound,
+        description: "rows of the arena, other maps have their own size",
+    },
+    Cvar {
+        name: COLS,
+        ty: Type::Int { min: 4, max: 500 },
+        default: Value::Int(20),
+        apply: Apply::NextRound,
+        description: "columns of the arena, other maps have their own size",
+    },
+    Cvar {
+        name: TICK_RATE,
+        ty: Type::Int { min: 1, max: 60 },
+        default: Value::Int(TICKS_PER_SECOND as i64),
+        apply: Apply::AtStart,
+        description: "how many times per second the game advances",
+    },
+    Cvar {
+        name: VIEW_RADIUS,
+        ty: Type::Int { min: 1, max: 100 },
+        default: Value::Int(12),
+        apply: Apply::Now,
+        description: "how far players see around their snake",
+    },
+    Cvar {
+        name: PROP_SPAWN_TICKS,
+        ty: Type::Int { min: 1, max: 1000 },
+        default: Value::Int(5),
+        apply: Apply::NextRound,
+        description: "ticks between food spawning",
+    },
+    Cvar {
+        name: SPAWN_TICKS,
+        ty: Type::Int { min: 0, max: 100 },
+        default: Value::Int(5),
+        apply: Apply::NextRound,
+        description: "ticks new snakes stay put and can not die",
+    },
+    Cvar {
+        name: DEATH_TICKS,
+        ty: Type::Int { min: 0, max: 100 },
+        default: Value::Int(0),
+        apply: Apply::NextRound,
+        description: "ticks dead snakes wait to respawn after decaying",
+    },
+    Cvar {
+        name: DECAY_ON_REMOVE,
+        ty: Type::Bool,
+        default: Value::Bool(true),
+        apply: Apply::NextRound,
+        description: "whether snakes of players who leave decay or vanish",
+    },
+    Cvar {
+        name: RCON_PASSWORD,
+        ty: Type::Password,
+        default: Value::Str(String::new()),
+        apply: Apply::Now,
+        description: "lets players run any command, rcon is off if empty",
+    },
+];
+
+fn find(name: &str) -> Option<&'static Cvar> {
+    CVARS.iter().find(|cvar| cvar.name == name)
+}
+
+impl Cvar {
+    // Fails with a message for whoever gave the value.
+    fn parse(&self, value: &str) -> Result<Value, String> {
+        match self.ty {
+            Type::Int { min, max } => match value.parse() {
+                Ok(n) if min <= n && n <= max => Ok(Value::Int(n)),
+
+                Ok(_) => Err(format!(
+                    "{} must be between {} and {}",
+                    self.name, min, max
+                )),
+
+                Err(_) => Err(format!("{} is not a number", value)),
+            },
+
+            Type::Bool => match value {
+                "1" | "true" | "on" => Ok(Value::Bool(true)),
+                "0" | "false" | "off" => Ok(Value::Bool(false)),
+                _ => Err(format!("{} is not true or false", value)),
+            },
+
+            Type::Password => Ok(Value::Str(value.to_string())),
+        }
+    }
+}
+
+// The values of all cvars, along with the changes that wait for the next
+// round.
+pub struct Cvars {
+    values: HashMap<&'static str, Value>,
+    pending: HashMap<&'static str, Value>,
+    is_running: bool,
+}
+
+impl Cvars {
+    pub fn new() -> Self {
+        Cvars {
+            values: CVARS
+                .iter()
+                .map(|c| (c.name, c.default.clone()))
+                .collect(),
+            pending: HashMap::new(),
+            is_running: false,
+        }
+    }
+
+    // From now on changes take effect as given by Cvar::apply.
+    pub fn start(&mut self) {
+        self.is_running = true;
+    }
+
+    pub fn get(&self, name: &str) -> &Value {
+        match self.values.get(name) {
+            Some(value) => value,
+            None => panic!("error @ Cvars::get: no cvar {}", name),
+        }
+    }
+
+    pub fn int(&self, name: &str) -> i64 {
+        match self.get(name) {
+            Value::Int(n) => *n,
+            _ => panic!("error @ Cvars::int: {} is not a number", name),
+        }
+    }
+
+    // Ints are never negative where sizes are asked for, see CVARS.
+    pub fn usize(&self, name: &str) -> usize {
+        self.int(name) as usize
+    }
+
+    pub fn bool(&self, name: &str) -> bool {
+        match self.get(name) {
+            Value::Bool(b) => *b,
+            _ => panic!("error @ Cvars::bool: {} is not a bool", name),
+        }
+    }
+
+    pub fn str(&self, name: &str) -> &str {
+        match self.get(name) {
+            Value::Str(s) => s,
+            _ => panic!("error @ Cvars::str: {} is not a string", name),
+        }
+    }
+
+    // The value for printing, passwords are hidden.
+    pub fn show(&self, name: &str) -> String {
+        match find(name) {
+            Some(cvar) if cvar.ty == Type::Password => "***".to_string(),
+            _ => self.get(name).to_string(),
+        }
+    }
+
+    // Returns whether the change took effect at once, or fails with a
+    // message for whoever made it.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<bool, String> {
+        let cvar = match find(name) {
+            Some(cvar) => cvar,
+            None => return Err(format!("unknown cvar {}, try cvars", name)),
+        };
+
+        let value = cvar.parse(value)?;
+
+        if !self.is_running {
+            self.values.insert(cvar.name, value);
+            return Ok(true);
+        }
+
+        match cvar.apply {
+            Apply::Now => {
+                self.values.insert(cvar.name, value);
+                Ok(true)
+            }
+
+            Apply::NextRound => {
+                self.pending.insert(cvar.name, value);
+                Ok(false)
+            }
+
+            Apply::AtStart => {
+                Err(format!("{} can only be set when starting", name))
+            }
+        }
+    }
+
+    // Applies the changes that waited for a new round, returning the names
+    // of the cvars that changed.
+    pub fn new_round(&mut self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+
+        for (name, value) in self.pending.drain() {
+            if self.values.get(name) != Some(&value) {
+                self.values.insert(name, value);
+                changed.push(name);
+            }
+        }
+
+        changed.sort();
+        changed
+    }
+
+    // Reads a config file, one "<cvar> <value>" per line. Empty lines and
+    // lines starting with // are skipped.
+    pub fn exec(&mut self, config: &str) -> Result<(), String> {
+        for (k, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            let res = match words.as_slice() {
+                [name, value] => self.set(name, value).map(|_| ()),
+                _ => Err("expected <cvar> <value>".to_string()),
+            };
+
+            res.map_err(|e| format!("line {}: {}", k + 1, e))?;
+        }
+
+        Ok(())
+    }
+
+    // One line per cvar with its value, for the cvars command.
+    pub fn describe(&self) -> String {
+        CVARS
+            .iter()
+            .map(|cvar| {
+                // along with what it will be next round
+                let value = self.show(cvar.name);
+                let value = match self.pending.get(cvar.name) {
+                    Some(next) => format!("{} -> {}", value, next),
+                    None => value,
+                };
+
+                format!("{:<18} {:<8} {}", cvar.name, value, cvar.description)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// +++++++++
+// + Tests +
+// +++++++++
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running() -> Cvars {
+        let mut cvars = Cvars::new();
+        cvars.start();
+        cvars
+    }
+
+    #[test]
+    fn ints_stay_within_bounds() {
+        let mut cvars = Cvars::new();
+
+        let err = "cols must be between 4 and 500".to_string();
+        assert_eq!(cvars.set(COLS, "3"), Err(err.clone()));
+        assert_eq!(cvars.set(COLS, "501"), Err(err));
+        assert_eq!(cvars.set(COLS, "wide"), Err("wide is not a number".into()));
+        assert_eq!(cvars.int(COLS), 20);
+
+        assert_eq!(cvars.set(COLS, "4"), Ok(true));
+        assert_eq!(cvars.set(COLS, "500"), Ok(true));
+        assert_eq!(cvars.int(COLS), 500);
+    }
+
+    #[test]
+    fn unknown_cvars_are_refused() {
+        let mut cvars = Cvars::new();
+        let err = "unknown cvar speed, try cvars".to_string();
+        assert_eq!(cvars.set("speed", "1"), Err(err));
+    }
+
+    #[test]
+    fn tick_rate_is_only_set_when_starting() {
+        let mut cvars = Cvars::new();
+        assert_eq!(cvars.set(TICK_RATE, "30"), Ok(true));
+
+        cvars.start();
+        let err = "tick_rate can only be set when starting".to_string();
+        assert_eq!(cvars.set(TICK_RATE, "10"), Err(err));
+        assert_eq!(cvars.int(TICK_RATE), 30);
+
+        // not even for the next round
+        assert!(cvars.new_round().is_empty());
+        assert_eq!(cvars.int(TICK_RATE), 30);
+    }
+
+    #[test]
+    fn next_round_values_wait_for_a_new_round() {
+        let mut cvars = running();
+
+        assert_eq!(cvars.set(ROWS, "30"), Ok(false));
+        assert_eq!(cvars.int(ROWS), 20);
+        assert!(cvars.describe().contains("20 -> 30"));
+
+        assert_eq!(cvars.new_round(), vec![ROWS]);
+        assert_eq!(cvars.int(ROWS), 30);
+
+        // values that do not change are not reported
+        assert_eq!(cvars.set(ROWS, "30"), Ok(false));
+        assert!(cvars.new_round().is_empty());
+    }
+
+    #[test]
+    fn now_values_take_effect_at_once() {
+        let mut cvars = running();
+        assert_eq!(cvars.set(VIEW_RADIUS, "5"), Ok(true));
+        assert_eq!(cvars.int(VIEW_RADIUS), 5);
+    }
+
+    #[test]
+    fn exec_a_config() {
+        let mut cvars = Cvars::new();
+        let config = "
+            // a small arena
+            rows 10
+
+            cols 12
+            decay_on_remove off
+        ";
+
+        assert_eq!(cvars.exec(config), Ok(()));
+        assert_eq!(cvars.int(ROWS), 10);
+        assert_eq!(cvars.int(COLS), 12);
+        assert!(!cvars.bool(DECAY_ON_REMOVE));
+    }
+
+    #[test]
+    fn exec_stops_at_a_bad_line() {
+        let mut cvars = Cvars::new();
+
+        let config = "rows 10\ncols\ncols 12";
+        let err = "line 2: expected <cvar> <value>".to_string();
+        assert_eq!(cvars.exec(config), Err(err));
+        assert_eq!(cvars.int(ROWS), 10);
+        assert_eq!(cvars.int(COLS), 20);
+
+        let config = "// comment\nrows 2";
+        let err = "line 2: rows must be between 4 and 500".to_string();
+        assert_eq!(cvars.exec(config), Err(err));
+
+        let err = "line 1: maybe is not true or false".to_string();
+        assert_eq!(cvars.exec("decay_on_remove maybe"), Err(err));
+    }
+
+    #[test]
+    fn passwords_are_hidden() {
+        let mut cvars = running();
+        assert_eq!(cvars.set(RCON_PASSWORD, "hunter2"), Ok(true));
+        assert_eq!(cvars.str(RCON_PASSWORD), "hunter2");
+
+        assert_eq!(cvars.show(RCON_PASSWORD), "***");
+        assert!(!cvars.describe().contains("hunter2"));
+
+        let line = cvars
+            .describe()
+            .lines()
+            .find(|line| line.starts_with(RCON_PASSWORD))
+            .unwrap()
+            .to_string();
+        assert!(line.contains("***"), "{}", line);
+    }
+}
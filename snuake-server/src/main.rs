@@ -2,20 +2,83 @@ extern crate futures;
 extern crate snuake_shared;
 extern crate tokio;
 
+mod console;
 mod core;
+mod cvar;
 mod websocket;
+mod stdin;
 mod streamext;
 
 use std::env;
+use std::fs;
+use std::process;
 
+use futures::future;
 use futures::prelude::*;
 use tokio::net::TcpListener;
 
+use cvar::Cvars;
+
+// another way to give the rcon password, one that keeps it off the command
+// line
+const RCON_PASSWORD_VAR: &str = "SNUAKE_RCON_PASSWORD";
+
+// Reads the cvars from the environment and the command line, see main.
+// Fails with a message for whoever started the server.
+fn cvars(args: &[String]) -> Result<Cvars, String> {
+    let mut cvars = Cvars::new();
+    let mut args = args.iter();
+
+    if let Ok(password) = env::var(RCON_PASSWORD_VAR) {
+        cvars.set(cvar::RCON_PASSWORD, &password)?;
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "+exec" => {
+                let path = args.next().ok_or("usage: +exec <file>")?;
+                fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|config| cvars.exec(&config))
+                    .map_err(|e| format!("{}: {}", path, e))?;
+            }
+
+            "+set" => match (args.next(), args.next()) {
+                (Some(name), Some(value)) => {
+                    cvars.set(name, value)?;
+                }
+                _ => return Err("usage: +set <cvar> <value>".to_string()),
+            },
+
+            arg => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    Ok(cvars)
+}
+
+// snuake-server [addr] [+exec <file>] [+set <cvar> <value>]...
+// Config files and cvars are applied in the order given, after the rcon
+// password from the environment.
 fn main() {
-    let addr = env::args().nth(1).unwrap_or("127.0.0.1:8080".to_string());
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (addr, args) = match args.first() {
+        Some(addr) if !addr.starts_with('+') => (addr.as_str(), &args[1..]),
+        _ => ("127.0.0.1:8080", &args[..]),
+    };
     let addr = addr.parse().unwrap();
 
-    let (core_s, c) = core::core();
+    let cvars = match cvars(args) {
+        Ok(cvars) => cvars,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let (core_s, c) = core::core(cvars);
+    let console = stdin::new(core_s.clone());
 
     let socket = TcpListener::bind(&addr).unwrap();
     println!("Listening on: {}", addr);
@@ -30,5 +93,10 @@ fn main() {
 
     let all = srv.select(c)
         .then(|_| Ok(()) );
-    tokio::run(all);
+
+    // the server keeps running when stdin is closed
+    tokio::run(future::lazy(move || {
+        tokio::spawn(console);
+        all
+    }));
 }
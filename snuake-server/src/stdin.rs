@@ -0,0 +1,19 @@
+use std::io::BufReader;
+
+use futures::prelude::*;
+use tokio::sync::mpsc::UnboundedSender as mpscUS;
+
+use crate::core::Event;
+use crate::streamext::StreamExt;
+
+// Lines typed into the server are console commands, run as Admin with
+// their output on stdout. Ends with stdin.
+pub fn new(core_s: mpscUS<Event>) -> impl Future<Item = (), Error = ()> {
+    tokio::io::lines(BufReader::new(tokio::io::stdin()))
+        .end_on_error()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Event::CCmd(None, line))
+        .map_err(|_| ())
+        .forward(core_s.sink_map_err(|_| ()))
+        .map(|_| ())
+}
@@ -3,6 +3,8 @@ extern crate tokio_tungstenite;
 use futures::Sink;
 use futures::future::FutureResult;
 
+use std::net::SocketAddr;
+
 use tokio::executor::Spawn;
 use tokio::net::TcpStream;
 use tokio::prelude::*;
@@ -17,6 +19,32 @@ use snuake_shared::*;
 use crate::streamext::StreamExt;
 use crate::core::Event;
 
+fn event(addr: SocketAddr, msg: ClientMsg) -> Event {
+    match msg {
+        ClientMsg::Hello {
+            protocol_version,
+            client_name,
+            player_name,
+            capabilities,
+        } => Event::Hello(
+            addr,
+            protocol_version,
+            client_name,
+            player_name,
+            capabilities,
+        ),
+        ClientMsg::Rename(name)  => Event::Rename(addr, name),
+        ClientMsg::Chat { text } => Event::Chat(addr, text),
+        ClientMsg::Pong(u)       => Event::Pong(addr, u),
+        ClientMsg::ConsoleCmd(s) => Event::CCmd(Some(addr), s),
+        ClientMsg::RconAuth(s)   => Event::RconAuth(addr, s),
+        ClientMsg::UserCmd { seq, tick, cmd } => {
+            Event::UCmd(addr, seq, tick, cmd)
+        }
+        ClientMsg::Ping(u)       => Event::Ping(addr, u)
+    }
+}
+
 pub fn new(s: TcpStream, core_s: mpscUS<Event>) -> Spawn {
     let addr = s.peer_addr().unwrap();
 
@@ -25,24 +53,33 @@ pub fn new(s: TcpStream, core_s: mpscUS<Event>) -> Spawn {
         let (ws_send, ws_recv) = ws.split();
         let (ch_send, ch_recv) = tokio::sync::mpsc::unbounded_channel();
 
-        // WebSocket messages
+        // WebSocket messages, those that can not be read are skipped
+        // unless they are the first
+        let mut is_first = true;
         let messages = ws_recv
             .end_on_error()
-            .filter_map(|msg|{
-                if let Message::Text(msg) = msg {
-                    Some(serde_json::from_str(&msg).unwrap())
-                } else {
-                    None
+            .filter_map(move |msg| {
+                let frame = match msg {
+                    Message::Text(s) => Frame::Text(s),
+                    Message::Binary(bytes) => Frame::Binary(bytes),
+                    _ => return None,
+                };
+
+                let is_hello = std::mem::replace(&mut is_first, false);
+
+                match frame.decode() {
+                    Some(msg) => Some(event(addr, msg)),
+
+                    None if is_hello => {
+                        let version = frame
+                            .decode::<Handshake>()
+                            .and_then(|h| h.protocol_version());
+                        Some(Event::BadHello(addr, version))
+                    }
+
+                    None => None,
                 }
             })
-            .map({ let addr = addr.clone(); move |msg|{
-                match msg {
-                    ClientMsg::Join          => Event::Join(addr),
-                    ClientMsg::ConsoleCmd(s) => Event::CCmd(Some(addr), s),
-                    ClientMsg::UserCmd(ucmd) => Event::UCmd(addr, ucmd),
-                    ClientMsg::Ping(u)       => Event::Ping(addr, u)
-                }
-            }})
             .map_err(|_|());
 
         // On open + WebSocket msgs + On close -> Core
@@ -54,12 +91,22 @@ pub fn new(s: TcpStream, core_s: mpscUS<Event>) -> Spawn {
             .map(|_| ());
 
 
-        // Forwards messages from core to client
+        // Forwards messages from core to client, in JSON until the welcome
+        // says otherwise
+        let mut encoding = Encoding::Json;
         let core_to_client = ch_recv
             .map_err(|_| ())
-            .filter_map(|msg| {
-                let msg = serde_json::to_string(&msg).ok()?;
-                Some(Message::Text(msg))
+            .filter_map(move |msg| {
+                let frame = encoding.encode(&msg)?;
+
+                if let ServerMsg::Welcome { ref capabilities, .. } = msg {
+                    encoding = Encoding::negotiated(capabilities);
+                }
+
+                match frame {
+                    Frame::Text(s) => Some(Message::Text(s)),
+                    Frame::Binary(bytes) => Some(Message::Binary(bytes)),
+                }
             })
             .forward(ws_send.sink_map_err(|_|()))
             .map(|_| ());
@@ -1,31 +1,252 @@
+extern crate bincode;
 extern crate serde;
+extern crate serde_json;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use saas::util::Direction;
 pub use saas::entity::PropID;
 pub use saas::entity::SnakeID;
+pub use saas::entity::TeamID;
 use saas::state::GameData;
 
+// Bumped whenever the messages below change in a way that older clients
+// or servers can not understand.
+pub const PROTOCOL_VERSION: u32 = 8;
+
+// The tick rate servers run at unless told otherwise. Clients use the one
+// announced in ServerMsg::Welcome.
 pub const TICKS_PER_SECOND: u64 = 8;
 
-#[derive(Debug, Serialize, Deserialize)]
+// Snakes are drawn in these colours, picked by SnakeID, unless they play in
+// teams.
+pub const PLAYER_COLORS: &[&str] = &[
+    "#83bf4f",
+    "#89664c",
+    "#555e63",
+    "#f29a2e",
+    "#f5d1ac",
+    "#e5bc5e",
+    "#947151",
+    "#4c5359",
+    "#f29a2e",
+    "#fc97b2",
+    "#89664c",
+    "#86959c",
+];
+
+// longer player names are cut short
+pub const MAX_NAME_LEN: usize = 16;
+
+// longer chat messages are cut short
+pub const MAX_CHAT_LEN: usize = 200;
+
+// Numbers the user commands of a client, counting up from 0.
+pub type Seq = u32;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum UserCmd {
     Direction(Direction),
+    Boost,
+}
+
+// What the server tells a client about the game it joined.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapInfo {
+    pub rows: usize,
+    pub cols: usize,
+    // how far players see around their snake
+    pub view_radius: usize,
+}
+
+// Someone playing on the server, see ServerMsg::Roster.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Player {
+    pub id: SnakeID,
+    pub name: String,
+    pub color: String,
+    pub team: Option<TeamID>,
+    pub score: u64,
+    // round trip time in milliseconds, once the server has measured it
+    pub ping: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RosterChange {
+    Joined(Player),
+    Left(SnakeID),
+    Renamed(SnakeID, String),
 }
 
 // From clients to server
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ClientMsg {
     Ping(usize),
-    Join,
+    // The first message of a client. Capabilities are optional features
+    // the client supports, the server answers with the ones it will use.
+    Hello {
+        protocol_version: u32,
+        client_name: String,
+        player_name: String,
+        capabilities: Vec<String>,
+    },
+    Rename(String),
+    Chat {
+        text: String,
+    },
+    // answers ServerMsg::Ping
+    Pong(usize),
+    // a line for the server's console, see ServerMsg::ConsoleOutput
     ConsoleCmd(String),
-    UserCmd(UserCmd),
+    // Lets the connection run every console command if the password is
+    // right. Answered with a ServerMsg::ConsoleOutput.
+    RconAuth(String),
+    // tick is the last one the client had heard of when it gave the command
+    UserCmd {
+        seq: Seq,
+        tick: u64,
+        cmd: UserCmd,
+    },
+}
+
+// Just enough of the first message of a client to tell which protocol it
+// speaks, for rejecting clients whose ClientMsg::Hello can not be read.
+// Clients from before the Hello sent a Join.
+#[derive(Debug, Deserialize)]
+pub enum Handshake {
+    Hello { protocol_version: u32 },
+    Join,
+}
+
+impl Handshake {
+    // None for clients from before protocol versions
+    pub fn protocol_version(&self) -> Option<u32> {
+        match self {
+            Handshake::Hello { protocol_version } => Some(*protocol_version),
+            Handshake::Join => None,
+        }
+    }
 }
 
 // From server to clients
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerMsg {
     Pong(usize),
-    GameData(GameData),
+    // for measuring round trip times, see ClientMsg::Pong
+    Ping(usize),
+    Welcome {
+        server_version: String,
+        protocol_version: u32,
+        tick_rate: u64,
+        map_info: MapInfo,
+        capabilities: Vec<String>,
+    },
+    // The client can not join or has been kicked, the server closes the
+    // connection after it.
+    Rejected(String),
+    // The game after the given tick, with the last user command of the
+    // client that had been applied by then.
+    GameData {
+        tick: u64,
+        ack: Option<Seq>,
+        game_data: GameData,
+    },
     NewID(SnakeID),
+    // Everyone playing. Sent after joining, then every second to keep the
+    // scores and pings current.
+    Roster(Vec<Player>),
+    RosterChange(RosterChange),
+    // From a player, or from the server itself when from is None. Sent at
+    // ts milliseconds since the Unix epoch.
+    Chat {
+        from: Option<SnakeID>,
+        text: String,
+        ts: u64,
+    },
+    // what a ClientMsg::ConsoleCmd printed, possibly several lines
+    ConsoleOutput(String),
+}
+
+// ++++++++
+// + Rcon +
+// ++++++++
+
+// Whether the guess is the rcon password, see ClientMsg::RconAuth. Takes as
+// long for any guess of the same length, whatever the password is, so that
+// the time it takes gives neither the password nor its length away.
+pub fn check_password(password: &str, guess: &str) -> bool {
+    let (a, b) = (password.as_bytes(), guess.as_bytes());
+
+    // guesses of another length are wrong whatever their bytes
+    let diff = b.iter().enumerate().fold(a.len() ^ b.len(), |diff, (k, y)| {
+        let x = a.get(k).cloned().unwrap_or(0);
+        diff | (x ^ y) as usize
+    });
+
+    diff == 0
+}
+
+// +++++++++++++
+// + Encodings +
+// +++++++++++++
+
+// The capability for sending messages as bincode in binary frames once the
+// handshake is done. Without it everything is JSON in text frames, which
+// is what wstest.html speaks. The handshake itself is always JSON.
+pub const BINCODE: &str = "bincode";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    Bincode,
+}
+
+// A WebSocket message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Encoding {
+    // The encoding to use with the capabilities from ServerMsg::Welcome.
+    pub fn negotiated(capabilities: &[String]) -> Self {
+        if capabilities.iter().any(|c| c == BINCODE) {
+            Encoding::Bincode
+        } else {
+            Encoding::Json
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, msg: &T) -> Option<Frame> {
+        match self {
+            Encoding::Json => serde_json::to_string(msg).ok().map(Frame::Text),
+
+            Encoding::Bincode => {
+                bincode::serialize(msg).ok().map(Frame::Binary)
+            }
+        }
+    }
+}
+
+impl Frame {
+    // Frames are read according to their kind, so either side can switch
+    // encodings without the other getting confused.
+    pub fn decode<T: DeserializeOwned>(&self) -> Option<T> {
+        match self {
+            Frame::Text(s) => serde_json::from_str(s).ok(),
+            Frame::Binary(bytes) => bincode::deserialize(bytes).ok(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Frame::Text(s) => s.len(),
+            Frame::Binary(bytes) => bytes.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
@@ -0,0 +1,97 @@
+extern crate saas;
+extern crate snuake_shared;
+
+use saas::state::*;
+use saas::util::*;
+use snuake_shared::*;
+
+// What a player sees of a busy game, as sent to them every tick.
+fn typical_game_data() -> GameData {
+    let mut st = GameState::builder().with_dimensions(32, 32).build();
+
+    let ids: Vec<SnakeID> =
+        (0..6).map(|_| st.add_snake().unwrap()).collect();
+
+    for _ in 0..20 {
+        st.tick();
+    }
+
+    let head = st.snake(ids[0]).unwrap().head();
+    st.get_game_data().unwrap().window(head, 12)
+}
+
+fn game_data_msg() -> ServerMsg {
+    ServerMsg::GameData {
+        tick: 1234,
+        ack: Some(56),
+        game_data: typical_game_data(),
+    }
+}
+
+fn user_cmd_msg(cmd: UserCmd) -> ClientMsg {
+    ClientMsg::UserCmd {
+        seq: 56,
+        tick: 1234,
+        cmd: cmd,
+    }
+}
+
+fn sizes<T: serde::Serialize>(msg: &T) -> (usize, usize) {
+    let json = Encoding::Json.encode(msg).unwrap();
+    let bincode = Encoding::Bincode.encode(msg).unwrap();
+    (json.len(), bincode.len())
+}
+
+#[test]
+fn game_data_is_smaller_in_bincode() {
+    let msg = game_data_msg();
+    let (json, bincode) = sizes(&msg);
+    assert!(bincode < json);
+}
+
+#[test]
+fn user_cmds_are_smaller_in_bincode() {
+    let msg = user_cmd_msg(UserCmd::Direction(Direction::Left));
+    let (json, bincode) = sizes(&msg);
+    assert!(bincode < json);
+}
+
+#[test]
+fn game_data_round_trips() {
+    let msg = game_data_msg();
+
+    // compared as JSON values, which do not care about the order of maps
+    let expected = serde_json::to_value(&msg).unwrap();
+
+    for encoding in [Encoding::Json, Encoding::Bincode].iter() {
+        let frame = encoding.encode(&msg).unwrap();
+        let decoded: ServerMsg = frame.decode().unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), expected);
+    }
+}
+
+#[test]
+fn frames_decode_by_kind() {
+    let msg = user_cmd_msg(UserCmd::Boost);
+    let frame = Encoding::Bincode.encode(&msg).unwrap();
+
+    match frame.decode::<ClientMsg>() {
+        Some(ClientMsg::UserCmd {
+            seq: 56,
+            tick: 1234,
+            cmd: UserCmd::Boost,
+        }) => (),
+        other => panic!("decoded {:?}", other),
+    }
+
+    let garbage = Frame::Text("not json".to_string());
+    assert!(garbage.decode::<ClientMsg>().is_none());
+}
+
+#[test]
+fn bincode_is_negotiated() {
+    assert_eq!(Encoding::negotiated(&[]), Encoding::Json);
+
+    let capabilities = vec!["other".to_string(), BINCODE.to_string()];
+    assert_eq!(Encoding::negotiated(&capabilities), Encoding::Bincode);
+}
@@ -0,0 +1,26 @@
+extern crate snuake_shared;
+
+use snuake_shared::*;
+
+#[test]
+fn right_password() {
+    assert!(check_password("hunter2", "hunter2"));
+    assert!(check_password("", ""));
+}
+
+#[test]
+fn wrong_password() {
+    assert!(!check_password("hunter2", "hunter3"));
+    assert!(!check_password("hunter2", "Hunter2"));
+    assert!(!check_password("hunter2", ""));
+}
+
+#[test]
+fn other_lengths() {
+    // neither a prefix nor something starting with the password will do,
+    // not even with the missing bytes zeroed
+    assert!(!check_password("hunter2", "hunter"));
+    assert!(!check_password("hunter2", "hunter22"));
+    assert!(!check_password("hunter2", "hunter2\0"));
+    assert!(!check_password("hunter2\0", "hunter2"));
+}